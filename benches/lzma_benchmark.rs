@@ -1,57 +1,31 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId, Throughput};
-use lzma_sdk_sys::{Allocator, CLzmaEncProps, ELzmaFinishMode, ELzmaStatus, LZMA_PROPS_SIZE};
-use lzma_sdk_sys::{LzmaEnc_Create, LzmaEnc_Destroy, LzmaEnc_SetProps, LzmaEncProps_Init};
-use lzma_sdk_sys::{LzmaDecode, LzmaEncode, SZ_OK, SizeT, Byte};
-use std::{fs, ptr};
+use lzma_sdk_sys::{Allocator, ELzmaFinishMode, ELzmaStatus, LZMA_PROPS_SIZE};
+use lzma_sdk_sys::{LzmaEncoder, LzmaEncoderOptions};
+use lzma_sdk_sys::{LzmaDecode, SZ_OK, SizeT, Byte};
+use std::fs;
 
 fn compress_data(input: &[u8]) -> (Vec<u8>, Vec<u8>) {
-    let mut props = vec![0u8; LZMA_PROPS_SIZE as usize];
-    let mut props_size = LZMA_PROPS_SIZE as SizeT;
     let mut compressed = vec![0u8; input.len() * 2];
-    let mut compressed_size = compressed.len() as SizeT;
-    let alloc = Allocator::default();
-
-    unsafe {
-        let enc = LzmaEnc_Create(alloc.as_ref() as *const _);
-        assert!(!enc.is_null());
 
-        let mut enc_props = CLzmaEncProps::default();
-        LzmaEncProps_Init(&mut enc_props);
-        
-        // Set maximum compression level (9)
-        enc_props.level = 9;
+    // Maximum compression level (9)
+    let options = LzmaEncoderOptions::preset(9)
         // Use maximum dictionary size for best compression
-        enc_props.dictSize = 1 << 24; // 16MB dictionary
+        .dict_size(1 << 24) // 16MB dictionary
         // Use more fast bytes for better compression
-        enc_props.fb = 273;
+        .fast_bytes(273)
         // Use maximum search depth
-        enc_props.mc = 1 << 30;
+        .match_cycles(1 << 30)
         // Use single thread
-        enc_props.numThreads = 1;
+        .threads(1);
 
-        let res = LzmaEnc_SetProps(enc, &enc_props);
-        assert_eq!(res, SZ_OK as i32);
-
-        let res = LzmaEncode(
-            compressed.as_mut_ptr() as *mut Byte,
-            &mut compressed_size,
-            input.as_ptr() as *const Byte,
-            input.len() as SizeT,
-            &enc_props,
-            props.as_mut_ptr() as *mut Byte,
-            &mut props_size,
-            0,
-            ptr::null_mut(),
-            alloc.as_ref(),
-            alloc.as_ref(),
-        );
-        assert_eq!(res, SZ_OK as i32);
-
-        LzmaEnc_Destroy(enc, alloc.as_ref(), alloc.as_ref());
-    }
+    let mut encoder = LzmaEncoder::with_options(&options).expect("Failed to create encoder");
+    let props = encoder.write_properties().expect("Failed to write properties");
+    let compressed_size = encoder
+        .encode_slice(input, &mut compressed, false)
+        .expect("Compression failed");
 
-    compressed.truncate(compressed_size as usize);
-    (compressed, props)
+    compressed.truncate(compressed_size);
+    (compressed, props.to_vec())
 }
 
 fn decompress_data(compressed: &[u8], props: &[u8], original_size: usize) -> Vec<u8> {
@@ -68,7 +42,7 @@ fn decompress_data(compressed: &[u8], props: &[u8], original_size: usize) -> Vec
             compressed.as_ptr() as *const Byte,
             &mut source_len,
             props.as_ptr() as *const Byte,
-            LZMA_PROPS_SIZE as u32,
+            LZMA_PROPS_SIZE,
             ELzmaFinishMode::LZMA_FINISH_END,
             &mut status,
             alloc.as_ref(),
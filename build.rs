@@ -229,6 +229,20 @@ fn get_defines(info: &PlatformInfo) -> HashMap<&'static str, Define> {
     defines
 }
 
+/// Headers whose declarations are implemented in additional `.c` files without a header of their own.
+const EXTRA_SOURCES_FOR_HEADER: &[(&str, &[&str])] = &[
+    ("Xz", &["XzDec"]),
+    ("Bra", &["Bra86", "BraIA64"]),
+    ("7zTypes", &["7zStream"]),
+    ("7z", &["7zArcIn", "7zDec"]),
+    ("Ppmd7", &["Ppmd7Dec", "Ppmd7Enc"]),
+    ("Bcj2", &["Bcj2Enc"]),
+];
+
+/// Compilation units which have a hand-written assembly counterpart in `precompiled-asm`.
+/// When the assembly isn't linked, the portable `*Opt.c` files are compiled instead.
+const ASM_REPLACEABLE_SOURCES: &[&str] = &["7zCrcOpt", "XzCrc64Opt", "AesOpt", "Sha1Opt", "Sha256Opt"];
+
 /// Extracts source file paths from C/C++ include directives in a wrapper file.
 ///
 /// This function scans a given wrapper file for `#include` directives that reference
@@ -264,12 +278,24 @@ fn get_source_files_from_includes(wrapper_path: &str) -> Result<Vec<String>, Box
             if Path::new(&source).exists() {
                 sources.push(source);
             }
+
+            // Some headers are implemented across multiple compilation units.
+            for (header, extra_sources) in EXTRA_SOURCES_FOR_HEADER {
+                if *header == file_name {
+                    for extra in *extra_sources {
+                        let source = format!("7z/C/{}.c", extra);
+                        if Path::new(&source).exists() {
+                            sources.push(source);
+                        }
+                    }
+                }
+            }
         }
     }
 
     /*
-        TODO: Replace compilation units with assembly files
-        when enable-asm feature is used.
+        Compilation units replaced with assembly files when the enable-asm feature is used.
+        See `add_asm_files` and `add_opt_sources`.
 
         ifdef USE_X86_ASM
         $O/7zCrcOpt.o: ../../../Asm/x86/7zCrcOpt.asm
@@ -309,6 +335,27 @@ fn prefer_clang(build: &mut cc::Build) {
     }
 }
 
+/// Returns true if the precompiled x86 assembly objects are linked instead of the `*Opt.c` files.
+fn uses_precompiled_asm(build_info: &PlatformInfo) -> bool {
+    env::var("CARGO_FEATURE_ENABLE_ASM").is_ok() && (build_info.is_x64 || build_info.is_x86)
+}
+
+/// Adds the portable `*Opt.c` files for units used by the wrapper (e.g. `7zCrc.c` needs `7zCrcOpt.c`),
+/// unless they are provided by the precompiled assembly objects.
+fn add_opt_sources(sources: &mut Vec<String>, build_info: &PlatformInfo) {
+    if uses_precompiled_asm(build_info) {
+        return;
+    }
+
+    for opt in ASM_REPLACEABLE_SOURCES {
+        let base = format!("7z/C/{}.c", opt.trim_end_matches("Opt"));
+        let source = format!("7z/C/{}.c", opt);
+        if sources.contains(&base) && Path::new(&source).exists() {
+            sources.push(source);
+        }
+    }
+}
+
 fn add_asm_files(build: &mut cc::Build, build_info: &PlatformInfo) -> Result<(), Box<dyn std::error::Error>> {
     // Only add ASM files if enabled
     if !env::var("CARGO_FEATURE_ENABLE_ASM").is_ok() {
@@ -367,8 +414,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let mut build = cc::Build::new();
     prefer_clang(&mut build);
-    let source_files = get_source_files_from_includes("wrapper.h")?;
+    let mut source_files = get_source_files_from_includes("wrapper.h")?;
     let platform_info = PlatformInfo::new(&build.get_compiler());
+    add_opt_sources(&mut source_files, &platform_info);
     let defines = get_defines(&platform_info);
 
     // Apply defines to cc::Build
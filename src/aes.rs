@@ -0,0 +1,267 @@
+use crate::once::InitOnce;
+use crate::*;
+use alloc::boxed::Box;
+use core::{ptr, slice};
+
+/// Key size of AES-256 in bytes.
+pub const AES256_KEY_SIZE: usize = 32;
+
+const BLOCK_SIZE: usize = AES_BLOCK_SIZE as usize;
+
+/// Number of blocks converted at a time when `data` has to go through an aligned copy.
+const SCRATCH_BLOCKS: usize = 64;
+
+static TABLES: InitOnce = InitOnce::new();
+
+/// Generates the AES tables and selects the software or hardware implementations, exactly once.
+fn init_tables() {
+    TABLES.call(|| unsafe { AesGenTables() });
+}
+
+/// Returns true if AES runs on hardware instructions (AES-NI, ARMv8 crypto extensions).
+pub fn aes_hardware_accelerated() -> bool {
+    init_tables();
+    unsafe { g_Aes_SupportedFunctions_Flags & k_Aes_SupportedFunctions_HW != 0 }
+}
+
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+struct Block([u8; BLOCK_SIZE]);
+
+/// The `ivAes` array taken by the SDK: the IV or counter, followed by the key schedule.
+#[repr(C, align(16))]
+struct AesState([UInt32; AES_NUM_IVMRK_WORDS as usize]);
+
+impl AesState {
+    fn new(key: &[u8; AES256_KEY_SIZE], iv: &[u8; BLOCK_SIZE], decrypt: bool) -> Box<Self> {
+        init_tables();
+
+        let mut state = Box::new(Self([0; AES_NUM_IVMRK_WORDS as usize]));
+        unsafe {
+            let iv_aes = state.0.as_mut_ptr();
+            if decrypt {
+                Aes_SetKey_Dec(iv_aes.add(4), key.as_ptr(), AES256_KEY_SIZE as u32);
+            } else {
+                Aes_SetKey_Enc(iv_aes.add(4), key.as_ptr(), AES256_KEY_SIZE as u32);
+            }
+            AesCbc_Init(iv_aes, iv.as_ptr());
+        }
+        state
+    }
+
+    /// Runs `func` over whole blocks.
+    ///
+    /// The hardware variants use aligned loads, so data which isn't 16-byte aligned
+    /// goes through an aligned copy.
+    fn code(&mut self, func: AES_CODE_FUNC, data: &mut [u8]) {
+        let func = func.expect("AES functions are set by AesGenTables");
+        if data.as_ptr().cast::<Block>().is_aligned() {
+            unsafe { func(self.0.as_mut_ptr(), data.as_mut_ptr(), data.len() / BLOCK_SIZE) };
+            return;
+        }
+
+        let mut scratch = [Block([0; BLOCK_SIZE]); SCRATCH_BLOCKS];
+        for chunk in data.chunks_mut(SCRATCH_BLOCKS * BLOCK_SIZE) {
+            let aligned = unsafe { slice::from_raw_parts_mut(scratch.as_mut_ptr() as *mut u8, chunk.len()) };
+            aligned.copy_from_slice(chunk);
+            unsafe { func(self.0.as_mut_ptr(), aligned.as_mut_ptr(), chunk.len() / BLOCK_SIZE) };
+            chunk.copy_from_slice(aligned);
+        }
+    }
+}
+
+impl Drop for AesState {
+    fn drop(&mut self) {
+        // Don't leave the key schedule behind in freed memory.
+        for word in self.0.iter_mut() {
+            unsafe { ptr::write_volatile(word, 0) };
+        }
+    }
+}
+
+/// AES-256 in CBC mode, as used by `.7z` archive encryption.
+///
+/// Dispatches to the fastest implementation picked by `AesGenTables` (`g_AesCbc_Encode`/`g_AesCbc_Decode`).
+/// The IV chains across calls, so a message may be processed in several parts.
+pub struct Aes256Cbc {
+    state: Box<AesState>,
+    func: AES_CODE_FUNC,
+}
+
+impl Aes256Cbc {
+    /// Creates an AES-256-CBC encryptor.
+    pub fn encryptor(key: &[u8; AES256_KEY_SIZE], iv: &[u8; BLOCK_SIZE]) -> Self {
+        let state = AesState::new(key, iv, false);
+        Self {
+            state,
+            func: unsafe { g_AesCbc_Encode },
+        }
+    }
+
+    /// Creates an AES-256-CBC decryptor.
+    pub fn decryptor(key: &[u8; AES256_KEY_SIZE], iv: &[u8; BLOCK_SIZE]) -> Self {
+        let state = AesState::new(key, iv, true);
+        Self {
+            state,
+            func: unsafe { g_AesCbc_Decode },
+        }
+    }
+
+    /// Encrypts or decrypts `data` in place, depending on how this was created.
+    ///
+    /// # Returns
+    /// * `Result<(), LzmaError>` - [`LzmaError::Param`] if the length of `data` isn't a
+    ///   multiple of `AES_BLOCK_SIZE`. No padding is applied.
+    pub fn process(&mut self, data: &mut [u8]) -> Result<(), LzmaError> {
+        if !data.len().is_multiple_of(BLOCK_SIZE) {
+            return Err(LzmaError::Param);
+        }
+        self.state.code(self.func, data);
+        Ok(())
+    }
+}
+
+/// AES-256 in CTR mode (`g_AesCtr_Code`), as used by WinZip AES encryption.
+///
+/// Unlike NIST SP 800-38A, the counter is incremented before each block, as a
+/// 64-bit little endian number in the first 8 bytes of the IV.
+/// Encryption and decryption are the same operation.
+pub struct Aes256Ctr {
+    state: Box<AesState>,
+    func: AES_CODE_FUNC,
+    keystream: Block,
+    keystream_pos: usize,
+}
+
+impl Aes256Ctr {
+    /// Creates an AES-256-CTR cipher with the given initial counter block.
+    pub fn new(key: &[u8; AES256_KEY_SIZE], iv: &[u8; BLOCK_SIZE]) -> Self {
+        let state = AesState::new(key, iv, false);
+        Self {
+            state,
+            func: unsafe { g_AesCtr_Code },
+            keystream: Block([0; BLOCK_SIZE]),
+            keystream_pos: BLOCK_SIZE,
+        }
+    }
+
+    /// XORs `data` with the keystream in place. `data` may have any length; the rest of a
+    /// partially used keystream block carries over to the next call.
+    pub fn apply_keystream(&mut self, data: &mut [u8]) {
+        let leftover = (BLOCK_SIZE - self.keystream_pos).min(data.len());
+        let (head, data) = data.split_at_mut(leftover);
+        xor(head, &self.keystream.0[self.keystream_pos..]);
+        self.keystream_pos += leftover;
+
+        let (blocks, tail) = data.split_at_mut(data.len() - data.len() % BLOCK_SIZE);
+        self.state.code(self.func, blocks);
+
+        if !tail.is_empty() {
+            self.keystream = Block([0; BLOCK_SIZE]);
+            self.state.code(self.func, &mut self.keystream.0);
+            xor(tail, &self.keystream.0);
+            self.keystream_pos = tail.len();
+        }
+    }
+}
+
+fn xor(data: &mut [u8], keystream: &[u8]) {
+    for (byte, key) in data.iter_mut().zip(keystream) {
+        *byte ^= key;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex<const N: usize>(s: &str) -> [u8; N] {
+        let mut out = [0u8; N];
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).unwrap();
+        }
+        out
+    }
+
+    // NIST SP 800-38A, F.2.5 and F.2.6 (CBC-AES256).
+    const SP800_38A_KEY: &str = "603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4";
+    const SP800_38A_IV: &str = "000102030405060708090a0b0c0d0e0f";
+    const SP800_38A_PLAINTEXT: &str = "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+                                       30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710";
+    const SP800_38A_CIPHERTEXT: &str = "f58c4c04d6e5f1ba779eabfb5f7bfbd69cfc4e967edb808d679f777bc6702c7d\
+                                        39f23369a9d9bacfa530e26304231461b2eb05e2c39be9fcda6c19078c6a9d1b";
+
+    #[test]
+    fn test_aes256_cbc_known_answer() {
+        let key = hex::<32>(SP800_38A_KEY);
+        let iv = hex::<16>(SP800_38A_IV);
+        let plaintext = hex::<64>(SP800_38A_PLAINTEXT);
+        let ciphertext = hex::<64>(SP800_38A_CIPHERTEXT);
+
+        // In two parts, the IV chains across calls.
+        let mut data = plaintext;
+        let mut encryptor = Aes256Cbc::encryptor(&key, &iv);
+        encryptor.process(&mut data[..16]).unwrap();
+        encryptor.process(&mut data[16..]).unwrap();
+        assert_eq!(data, ciphertext);
+
+        Aes256Cbc::decryptor(&key, &iv).process(&mut data).unwrap();
+        assert_eq!(data, plaintext);
+
+        assert_eq!(encryptor.process(&mut [0u8; 15]), Err(LzmaError::Param));
+    }
+
+    #[test]
+    fn test_aes256_cbc_unaligned() {
+        let key = hex::<32>(SP800_38A_KEY);
+        let iv = hex::<16>(SP800_38A_IV);
+        let plaintext: Vec<u8> = (0..SCRATCH_BLOCKS as u32 * 48).map(|x| x as u8).collect();
+
+        let mut expected = plaintext.clone();
+        Aes256Cbc::encryptor(&key, &iv).process(&mut expected).unwrap();
+
+        let mut buffer = vec![0u8; plaintext.len() + 1];
+        buffer[1..].copy_from_slice(&plaintext);
+        Aes256Cbc::encryptor(&key, &iv).process(&mut buffer[1..]).unwrap();
+        assert_eq!(buffer[1..], expected[..]);
+
+        Aes256Cbc::decryptor(&key, &iv).process(&mut buffer[1..]).unwrap();
+        assert_eq!(buffer[1..], plaintext[..]);
+    }
+
+    #[test]
+    fn test_aes256_ctr_known_answer() {
+        // FIPS-197, C.3: the first counter block (the IV after its increment) is the plaintext,
+        // so the first keystream block is the ciphertext.
+        let key = hex::<32>("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f");
+        let iv = hex::<16>("ff102233445566778899aabbccddeeff");
+        let mut data = [0u8; 16];
+        Aes256Ctr::new(&key, &iv).apply_keystream(&mut data);
+        assert_eq!(data, hex::<16>("8ea2b7ca516745bfeafc49904b496089"));
+    }
+
+    #[test]
+    fn test_aes256_ctr_partial_blocks() {
+        let key = hex::<32>(SP800_38A_KEY);
+        let iv = hex::<16>(SP800_38A_IV);
+        let plaintext: Vec<u8> = (0..1000u32).map(|x| (x * 7) as u8).collect();
+
+        let mut expected = plaintext.clone();
+        Aes256Ctr::new(&key, &iv).apply_keystream(&mut expected);
+        assert_ne!(expected, plaintext);
+
+        let mut data = plaintext.clone();
+        let mut cipher = Aes256Ctr::new(&key, &iv);
+        let mut rest = &mut data[..];
+        for len in [1, 7, 8, 33, 16, 200] {
+            let (part, tail) = rest.split_at_mut(len);
+            cipher.apply_keystream(part);
+            rest = tail;
+        }
+        cipher.apply_keystream(rest);
+        assert_eq!(data, expected);
+
+        Aes256Ctr::new(&key, &iv).apply_keystream(&mut data);
+        assert_eq!(data, plaintext);
+    }
+}
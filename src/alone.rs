@@ -0,0 +1,231 @@
+use crate::*;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ptr;
+
+/// Size of the classic `.lzma` (LZMA-Alone) header: properties, dictionary size and decompressed size.
+pub const LZMA_ALONE_HEADER_SIZE: usize = LZMA_PROPS_SIZE as usize + 8;
+
+/// Size of the output chunks used when the decompressed size is unknown.
+const DECODE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Upper bound for the initial output allocation when the header announces the decompressed size.
+const MAX_INITIAL_CAPACITY: usize = 64 * 1024 * 1024;
+
+/// Header of the classic `.lzma` (LZMA-Alone) format, as produced by `xz --format=lzma` and `7z`.
+///
+/// ```ignore
+/// Offset  Size  Description
+/// 0       1     lc/lp/pb properties byte ((pb * 5 + lp) * 9 + lc)
+/// 1       4     Dictionary size (little endian)
+/// 5       8     Decompressed size (little endian), or -1 if unknown (stream has end marker)
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LzmaAloneHeader {
+    /// The 5-byte LZMA properties (properties byte + dictionary size).
+    pub props: [u8; LZMA_PROPS_SIZE as usize],
+    /// The decompressed size, or `None` if the stream is terminated by an end marker.
+    pub unpacked_size: Option<u64>,
+}
+
+impl LzmaAloneHeader {
+    /// Parses and validates the header at the start of `data`.
+    ///
+    /// # Returns
+    /// * `Result<Self, LzmaError>` - The header, [`LzmaError::InputEof`] if `data` is shorter than
+    ///   [`LZMA_ALONE_HEADER_SIZE`], or [`LzmaError::Unsupported`] if the properties are invalid.
+    pub fn parse(data: &[u8]) -> Result<Self, LzmaError> {
+        if data.len() < LZMA_ALONE_HEADER_SIZE {
+            return Err(LzmaError::InputEof);
+        }
+
+        let mut props = [0u8; LZMA_PROPS_SIZE as usize];
+        props.copy_from_slice(&data[..LZMA_PROPS_SIZE as usize]);
+
+        // Rejects lc/lp/pb values outside of the valid range.
+        let mut decoded = CLzmaProps::default();
+        check(unsafe { LzmaProps_Decode(&mut decoded, props.as_ptr(), props.len() as u32) })?;
+
+        let mut size = [0u8; 8];
+        size.copy_from_slice(&data[LZMA_PROPS_SIZE as usize..LZMA_ALONE_HEADER_SIZE]);
+        let size = u64::from_le_bytes(size);
+        let unpacked_size = if size == u64::MAX { None } else { Some(size) };
+
+        Ok(Self { props, unpacked_size })
+    }
+
+    /// Serializes the header into its 13-byte on-disk representation.
+    pub fn to_bytes(&self) -> [u8; LZMA_ALONE_HEADER_SIZE] {
+        let mut bytes = [0u8; LZMA_ALONE_HEADER_SIZE];
+        bytes[..LZMA_PROPS_SIZE as usize].copy_from_slice(&self.props);
+        bytes[LZMA_PROPS_SIZE as usize..].copy_from_slice(&self.unpacked_size.unwrap_or(u64::MAX).to_le_bytes());
+        bytes
+    }
+}
+
+/// Compresses `input` into a classic `.lzma` file with a known decompressed size.
+///
+/// # Arguments
+/// * `input` - Data to compress.
+/// * `props` - Encoder properties, initialize with `LzmaEncProps_Init`.
+pub fn compress_lzma_alone(input: &[u8], props: &CLzmaEncProps) -> Result<Vec<u8>, LzmaError> {
+    let alloc = Allocator::default();
+
+    // Worst case LZMA expansion for incompressible data is slightly above the input size.
+    let capacity = input.len() + input.len() / 3 + 128;
+    let mut output = vec![0u8; LZMA_ALONE_HEADER_SIZE + capacity];
+
+    let mut header = LzmaAloneHeader {
+        props: [0u8; LZMA_PROPS_SIZE as usize],
+        unpacked_size: Some(input.len() as u64),
+    };
+    let mut props_size = LZMA_PROPS_SIZE as SizeT;
+    let mut compressed_size = capacity as SizeT;
+    check(unsafe {
+        LzmaEncode(
+            output[LZMA_ALONE_HEADER_SIZE..].as_mut_ptr(),
+            &mut compressed_size,
+            input.as_ptr(),
+            input.len() as SizeT,
+            props,
+            header.props.as_mut_ptr(),
+            &mut props_size,
+            0,
+            ptr::null(),
+            alloc.as_ref(),
+            alloc.as_ref(),
+        )
+    })?;
+
+    output[..LZMA_ALONE_HEADER_SIZE].copy_from_slice(&header.to_bytes());
+    output.truncate(LZMA_ALONE_HEADER_SIZE + compressed_size);
+    Ok(output)
+}
+
+/// Decompresses a classic `.lzma` file, with either a known or unknown decompressed size.
+///
+/// # Returns
+/// * `Result<Vec<u8>, LzmaError>` - The decompressed data, or an error for invalid headers,
+///   corrupt data ([`LzmaError::Data`]) or truncated input ([`LzmaError::InputEof`]).
+pub fn decompress_lzma_alone(data: &[u8]) -> Result<Vec<u8>, LzmaError> {
+    let header = LzmaAloneHeader::parse(data)?;
+    let compressed = &data[LZMA_ALONE_HEADER_SIZE..];
+
+    match header.unpacked_size {
+        Some(size) => decompress_known_size(compressed, &header.props, size),
+        None => decompress_until_end_mark(compressed, &header.props),
+    }
+}
+
+fn decompress_known_size(mut compressed: &[u8], props: &[u8], size: u64) -> Result<Vec<u8>, LzmaError> {
+    let mut decoder = LzmaDecoder::new(props)?;
+
+    // The size comes from an untrusted header, so only use it as a hint for the first allocation.
+    let capacity = size
+        .min(compressed.len().saturating_mul(4) as u64)
+        .min(MAX_INITIAL_CAPACITY as u64);
+    let mut output = Vec::with_capacity(capacity as usize);
+
+    while (output.len() as u64) < size {
+        let start = output.len();
+        let remaining = size - start as u64;
+        let chunk = remaining.min(DECODE_CHUNK_SIZE as u64) as usize;
+        let finish_mode = if chunk as u64 == remaining {
+            ELzmaFinishMode::LZMA_FINISH_END
+        } else {
+            ELzmaFinishMode::LZMA_FINISH_ANY
+        };
+
+        output.resize(start + chunk, 0);
+        let progress = decoder.decode(compressed, &mut output[start..], finish_mode)?;
+        compressed = &compressed[progress.consumed..];
+        output.truncate(start + progress.produced);
+
+        // Also reached if the end marker comes before the announced size.
+        if progress.consumed == 0 && progress.produced == 0 {
+            return Err(LzmaError::InputEof);
+        }
+    }
+
+    Ok(output)
+}
+
+fn decompress_until_end_mark(mut compressed: &[u8], props: &[u8]) -> Result<Vec<u8>, LzmaError> {
+    let mut decoder = LzmaDecoder::new(props)?;
+    let mut output = Vec::new();
+
+    loop {
+        let start = output.len();
+        output.resize(start + DECODE_CHUNK_SIZE, 0);
+        let progress = decoder.decode(compressed, &mut output[start..], ELzmaFinishMode::LZMA_FINISH_ANY)?;
+        compressed = &compressed[progress.consumed..];
+        output.truncate(start + progress.produced);
+
+        if progress.is_finished() {
+            return Ok(output);
+        }
+        if progress.consumed == 0 && progress.produced == 0 {
+            return Err(LzmaError::InputEof);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `Hello from xz --format=lzma!\n` repeated 4 times, compressed by liblzma (unknown size, end marker).
+    const XZ_LZMA_FIXTURE: [u8; 55] = [
+        0x5D, 0x00, 0x00, 0x80, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x24, 0x19, 0x49, 0x98,
+        0x6F, 0x10, 0x15, 0x88, 0x4C, 0x89, 0xFC, 0x9A, 0xAA, 0xA6, 0x48, 0xCC, 0xCA, 0x72, 0xAC, 0x94, 0x1D, 0x9D,
+        0x01, 0x1C, 0x52, 0xA5, 0xF9, 0xAA, 0xDF, 0xDF, 0x60, 0x82, 0x1D, 0x07, 0xD7, 0xFF, 0xFF, 0xEB, 0x4D, 0x40,
+        0x00,
+    ];
+
+    #[test]
+    fn test_lzma_alone_round_trip() {
+        let input: Vec<u8> = (0..50_000u32).map(|x| (x % 253) as u8 ^ (x >> 9) as u8).collect();
+        let mut props = CLzmaEncProps::default();
+        unsafe { LzmaEncProps_Init(&mut props) };
+
+        let file = compress_lzma_alone(&input, &props).unwrap();
+        let header = LzmaAloneHeader::parse(&file).unwrap();
+        assert_eq!(header.unpacked_size, Some(input.len() as u64));
+        assert_eq!(decompress_lzma_alone(&file).unwrap(), input);
+    }
+
+    #[test]
+    fn test_lzma_alone_decodes_xz_output() {
+        let header = LzmaAloneHeader::parse(&XZ_LZMA_FIXTURE).unwrap();
+        assert_eq!(header.unpacked_size, None);
+        assert_eq!(header.to_bytes(), XZ_LZMA_FIXTURE[..LZMA_ALONE_HEADER_SIZE]);
+
+        let output = decompress_lzma_alone(&XZ_LZMA_FIXTURE).unwrap();
+        assert_eq!(output, b"Hello from xz --format=lzma!\n".repeat(4));
+    }
+
+    #[test]
+    fn test_lzma_alone_rejects_bad_headers() {
+        assert_eq!(LzmaAloneHeader::parse(&XZ_LZMA_FIXTURE[..12]), Err(LzmaError::InputEof));
+
+        let mut bad_props = XZ_LZMA_FIXTURE;
+        bad_props[0] = 225;
+        assert_eq!(decompress_lzma_alone(&bad_props), Err(LzmaError::Unsupported));
+
+        let truncated = &XZ_LZMA_FIXTURE[..XZ_LZMA_FIXTURE.len() - 10];
+        assert_eq!(decompress_lzma_alone(truncated), Err(LzmaError::InputEof));
+    }
+
+    #[test]
+    fn test_lzma_alone_rejects_crafted_size() {
+        // A huge announced size must fail cleanly instead of being allocated up front.
+        let mut crafted = XZ_LZMA_FIXTURE;
+        crafted[LZMA_PROPS_SIZE as usize..LZMA_ALONE_HEADER_SIZE]
+            .copy_from_slice(&0xFFFF_FFFF_FFFF_FFFEu64.to_le_bytes());
+        assert_eq!(decompress_lzma_alone(&crafted), Err(LzmaError::InputEof));
+
+        // An announced size past the end marker is truncated input as well.
+        crafted[LZMA_PROPS_SIZE as usize..LZMA_ALONE_HEADER_SIZE].copy_from_slice(&200u64.to_le_bytes());
+        assert_eq!(decompress_lzma_alone(&crafted), Err(LzmaError::InputEof));
+    }
+}
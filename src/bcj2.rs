@@ -0,0 +1,231 @@
+use crate::*;
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ptr;
+
+// Stream indices and coder states from the anonymous enums in `Bcj2.h`, which bindgen can't name.
+const BCJ2_STREAM_MAIN: usize = 0;
+const BCJ2_ENC_STATE_ORIG: u32 = BCJ2_NUM_STREAMS;
+const BCJ2_ENC_STATE_FINISHED: u32 = BCJ2_NUM_STREAMS + 1;
+
+/// The four output streams of the BCJ2 x86 filter.
+///
+/// In `.7z` archives these are stored as the four inputs of the BCJ2 coder, in field order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bcj2Streams {
+    /// Code with the targets of converted `call`/`jmp` instructions removed.
+    pub main: Vec<u8>,
+    /// Absolute big endian targets of `call` (`E8`) instructions.
+    pub call: Vec<u8>,
+    /// Absolute big endian targets of `jmp` (`E9`) and `jcc` (`0F 8x`) instructions.
+    pub jump: Vec<u8>,
+    /// Range coded flags telling which instructions were converted.
+    pub rc: Vec<u8>,
+}
+
+impl Bcj2Streams {
+    /// Returns the streams in `BCJ2_STREAM_*` order, as taken by [`Bcj2Decoder::decode`].
+    pub fn as_slices(&self) -> [&[u8]; BCJ2_NUM_STREAMS as usize] {
+        [&self.main, &self.call, &self.jump, &self.rc]
+    }
+
+    fn as_vecs(&mut self) -> [&mut Vec<u8>; BCJ2_NUM_STREAMS as usize] {
+        [&mut self.main, &mut self.call, &mut self.jump, &mut self.rc]
+    }
+}
+
+/// Streaming BCJ2 encoder (`CBcj2Enc`), splitting x86 code into four [`Bcj2Streams`].
+///
+/// BCJ2 gives better ratios than the plain x86 [`BranchFilter`] when each stream is
+/// compressed separately, as done by `7z` (usually LZMA for `main`, and small LZMA
+/// dictionaries for `call` and `jump`; `rc` is already entropy coded).
+pub struct Bcj2Encoder {
+    enc: Box<CBcj2Enc>,
+}
+
+impl Default for Bcj2Encoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bcj2Encoder {
+    /// Creates an encoder for a stream starting at address 0.
+    pub fn new() -> Self {
+        let mut enc = Box::<CBcj2Enc>::default();
+        unsafe { Bcj2Enc_Init(enc.as_mut()) };
+        Self { enc }
+    }
+
+    /// Encodes the next chunk of code, appending the produced data to `streams`.
+    ///
+    /// Up to 4 trailing bytes of an instruction may be held back until the next call.
+    pub fn encode(&mut self, input: &[u8], streams: &mut Bcj2Streams) {
+        self.run(input, EBcj2Enc_FinishMode::BCJ2_ENC_FINISH_MODE_CONTINUE, streams);
+    }
+
+    /// Encodes the held back bytes and flushes the range coder, ending the stream.
+    pub fn finish(mut self, streams: &mut Bcj2Streams) {
+        self.run(&[], EBcj2Enc_FinishMode::BCJ2_ENC_FINISH_MODE_END_STREAM, streams);
+    }
+
+    /// Encodes all of `input` in one go.
+    pub fn encode_all(input: &[u8]) -> Bcj2Streams {
+        let mut streams = Bcj2Streams::default();
+        let mut encoder = Self::new();
+        encoder.encode(input, &mut streams);
+        encoder.finish(&mut streams);
+        streams
+    }
+
+    fn run(&mut self, input: &[u8], finish_mode: EBcj2Enc_FinishMode, streams: &mut Bcj2Streams) {
+        let enc = self.enc.as_mut();
+        enc.src = input.as_ptr();
+        enc.srcLim = input.as_ptr_range().end;
+        enc.finishMode = finish_mode;
+
+        let done = match finish_mode {
+            EBcj2Enc_FinishMode::BCJ2_ENC_FINISH_MODE_END_STREAM => BCJ2_ENC_STATE_FINISHED,
+            _ => BCJ2_ENC_STATE_ORIG,
+        };
+
+        let mut outputs = streams.as_vecs();
+        let mut written = outputs.each_ref().map(|output| output.len());
+        // Worst case estimates for the whole chunk; a stream which still runs out is grown below.
+        let reserve = [input.len() + 16, input.len() / 4 + 16, input.len() / 4 + 16, input.len() / 16 + 16];
+        for (output, extra) in outputs.iter_mut().zip(reserve) {
+            output.resize(output.len() + extra, 0);
+        }
+
+        loop {
+            for (i, output) in outputs.iter_mut().enumerate() {
+                let range = output.as_mut_ptr_range();
+                enc.bufs[i] = unsafe { range.start.add(written[i]) };
+                enc.lims[i] = range.end;
+            }
+
+            unsafe { Bcj2Enc_Encode(enc) };
+
+            for (i, output) in outputs.iter().enumerate() {
+                written[i] = enc.bufs[i] as usize - output.as_ptr() as usize;
+            }
+
+            if enc.state == done {
+                break;
+            }
+            // The encoder stopped since stream `state` is full.
+            let full = &mut outputs[enc.state as usize];
+            full.resize(full.len() * 2, 0);
+        }
+
+        for (output, len) in outputs.iter_mut().zip(written) {
+            output.truncate(len);
+        }
+    }
+}
+
+// The state is exclusively owned and only ever accessed through `&mut self`.
+unsafe impl Send for Bcj2Encoder {}
+
+/// BCJ2 decoder (`CBcj2Dec`), merging the four [`Bcj2Streams`] back into x86 code.
+pub struct Bcj2Decoder {
+    dec: Box<CBcj2Dec>,
+}
+
+impl Default for Bcj2Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bcj2Decoder {
+    /// Creates a decoder.
+    pub fn new() -> Self {
+        Self {
+            dec: Box::default(),
+        }
+    }
+
+    /// Decodes the four streams, in `BCJ2_STREAM_*` order (see [`Bcj2Streams::as_slices`]).
+    ///
+    /// # Arguments
+    /// * `streams` - The complete main, call, jump and range coder streams.
+    /// * `unpacked_size` - Size of the original data, which BCJ2 doesn't store itself.
+    ///
+    /// # Returns
+    /// * `Result<Vec<u8>, LzmaError>` - The original data, or [`LzmaError::Data`] if the
+    ///   streams are corrupt, truncated, or not fully consumed.
+    pub fn decode(&mut self, streams: [&[u8]; BCJ2_NUM_STREAMS as usize], unpacked_size: usize) -> Result<Vec<u8>, LzmaError> {
+        let mut output = vec![0u8; unpacked_size];
+        let dec = self.dec.as_mut();
+        unsafe { Bcj2Dec_Init(dec) };
+        for (i, stream) in streams.iter().enumerate() {
+            let range = stream.as_ptr_range();
+            dec.bufs[i] = range.start;
+            dec.lims[i] = range.end;
+        }
+        let range = output.as_mut_ptr_range();
+        dec.dest = range.start;
+        dec.destLim = range.end;
+
+        check(unsafe { Bcj2Dec_Decode(dec) })?;
+
+        // Same checks as `7zDec.c`: all input consumed, and the decoder stopped after a
+        // complete instruction (`Bcj2Dec_IsMaybeFinished`) with the output full.
+        let consumed = (0..streams.len()).all(|i| dec.bufs[i] == dec.lims[i]);
+        let finished = dec.state == BCJ2_STREAM_MAIN as u32 && dec.code == 0;
+        if !consumed || !finished || !ptr::eq(dec.dest, dec.destLim) {
+            return Err(LzmaError::Data);
+        }
+        Ok(output)
+    }
+}
+
+// The state is exclusively owned and only ever accessed through `&mut self`.
+unsafe impl Send for Bcj2Decoder {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ELF: &[u8] = include_bytes!("../tests/fixtures/hello.elf");
+
+    #[test]
+    fn test_bcj2_round_trip_elf() {
+        let streams = Bcj2Encoder::encode_all(ELF);
+        assert!(!streams.call.is_empty());
+        assert!(!streams.rc.is_empty());
+        assert_eq!(streams.call.len() % 4, 0);
+        assert_eq!(streams.jump.len() % 4, 0);
+        assert_eq!(streams.main.len() + streams.call.len() + streams.jump.len(), ELF.len());
+
+        let decoded = Bcj2Decoder::new().decode(streams.as_slices(), ELF.len()).unwrap();
+        assert_eq!(decoded, ELF);
+    }
+
+    #[test]
+    fn test_bcj2_chunked_encode() {
+        let mut streams = Bcj2Streams::default();
+        let mut encoder = Bcj2Encoder::new();
+        for chunk in ELF.chunks(1000) {
+            encoder.encode(chunk, &mut streams);
+        }
+        encoder.finish(&mut streams);
+        assert_eq!(streams, Bcj2Encoder::encode_all(ELF));
+    }
+
+    #[test]
+    fn test_bcj2_corrupt_streams() {
+        let streams = Bcj2Encoder::encode_all(ELF);
+        let mut decoder = Bcj2Decoder::new();
+
+        let mut slices = streams.as_slices();
+        // Range coder stream.
+        slices[3] = &streams.rc[..streams.rc.len() - 1];
+        assert_eq!(decoder.decode(slices, ELF.len()), Err(LzmaError::Data));
+
+        // Too much output requested, the main stream runs out.
+        assert_eq!(decoder.decode(streams.as_slices(), ELF.len() + 1), Err(LzmaError::Data));
+    }
+}
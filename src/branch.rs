@@ -0,0 +1,250 @@
+use crate::*;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::{mem, slice};
+
+type BranchConvFn = unsafe extern "C" fn(*mut Byte, SizeT, UInt32) -> *mut Byte;
+
+/// Branch converter (BCJ filter) for executable code, from `Bra.c`, `Bra86.c` and `BraIA64.c`.
+///
+/// Converts relative branch targets into absolute addresses, which makes repeated calls
+/// to the same function look identical and improves the compression ratio of executables.
+/// Conversion happens in place, the size of the data never changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BranchFilter {
+    /// x86 and x86-64 (`z7_BranchConvSt_X86_*`).
+    X86,
+    /// Big endian PowerPC.
+    PowerPc,
+    /// Itanium.
+    Ia64,
+    /// Little endian ARM.
+    Arm,
+    /// ARM Thumb.
+    ArmThumb,
+    /// SPARC.
+    Sparc,
+    /// ARM64.
+    Arm64,
+    /// RISC-V.
+    RiscV,
+}
+
+impl BranchFilter {
+    /// Returns the instruction alignment in bytes; `ip` values should be multiples of it.
+    pub fn alignment(self) -> usize {
+        match self {
+            BranchFilter::X86 => 1,
+            BranchFilter::ArmThumb | BranchFilter::RiscV => 2,
+            BranchFilter::PowerPc | BranchFilter::Arm | BranchFilter::Sparc | BranchFilter::Arm64 => 4,
+            BranchFilter::Ia64 => 16,
+        }
+    }
+
+    /// Converts `data` in place for compression.
+    ///
+    /// # Arguments
+    /// * `data` - Code to convert.
+    /// * `ip` - Address of `data[0]` (the start offset within the file, usually 0).
+    ///
+    /// # Returns
+    /// * `usize` - Number of bytes converted. The remaining bytes hold a possibly partial
+    ///   instruction and are left unchanged; use [`BranchConverter`] to convert a stream in chunks.
+    pub fn encode(self, data: &mut [u8], ip: u32) -> usize {
+        let mut state = Z7_BRANCH_CONV_ST_X86_STATE_INIT_VAL;
+        self.convert(data, ip, &mut state, true)
+    }
+
+    /// Reverts [`BranchFilter::encode`] in place. See there for the arguments and return value.
+    pub fn decode(self, data: &mut [u8], ip: u32) -> usize {
+        let mut state = Z7_BRANCH_CONV_ST_X86_STATE_INIT_VAL;
+        self.convert(data, ip, &mut state, false)
+    }
+
+    fn convert(self, data: &mut [u8], ip: u32, x86_state: &mut u32, encoding: bool) -> usize {
+        let func: BranchConvFn = match (self, encoding) {
+            (BranchFilter::X86, true) => {
+                let end = unsafe { z7_BranchConvSt_X86_Enc(data.as_mut_ptr(), data.len(), ip, x86_state) };
+                return end as usize - data.as_ptr() as usize;
+            }
+            (BranchFilter::X86, false) => {
+                let end = unsafe { z7_BranchConvSt_X86_Dec(data.as_mut_ptr(), data.len(), ip, x86_state) };
+                return end as usize - data.as_ptr() as usize;
+            }
+            (BranchFilter::PowerPc, true) => z7_BranchConv_PPC_Enc,
+            (BranchFilter::PowerPc, false) => z7_BranchConv_PPC_Dec,
+            (BranchFilter::Ia64, true) => z7_BranchConv_IA64_Enc,
+            (BranchFilter::Ia64, false) => z7_BranchConv_IA64_Dec,
+            (BranchFilter::Arm, true) => z7_BranchConv_ARM_Enc,
+            (BranchFilter::Arm, false) => z7_BranchConv_ARM_Dec,
+            (BranchFilter::ArmThumb, true) => z7_BranchConv_ARMT_Enc,
+            (BranchFilter::ArmThumb, false) => z7_BranchConv_ARMT_Dec,
+            (BranchFilter::Sparc, true) => z7_BranchConv_SPARC_Enc,
+            (BranchFilter::Sparc, false) => z7_BranchConv_SPARC_Dec,
+            (BranchFilter::Arm64, true) => z7_BranchConv_ARM64_Enc,
+            (BranchFilter::Arm64, false) => z7_BranchConv_ARM64_Dec,
+            (BranchFilter::RiscV, true) => z7_BranchConv_RISCV_Enc,
+            (BranchFilter::RiscV, false) => z7_BranchConv_RISCV_Dec,
+        };
+
+        // The converters read whole instructions through aligned loads (`GetUi32a` & co.),
+        // so unaligned slices are converted in an aligned copy.
+        if (data.as_ptr() as *const u32).is_aligned() {
+            let end = unsafe { func(data.as_mut_ptr(), data.len(), ip) };
+            return end as usize - data.as_ptr() as usize;
+        }
+
+        let mut aligned = vec![0u32; data.len().div_ceil(mem::size_of::<u32>())];
+        let scratch = unsafe { slice::from_raw_parts_mut(aligned.as_mut_ptr() as *mut u8, data.len()) };
+        scratch.copy_from_slice(data);
+        let end = unsafe { func(scratch.as_mut_ptr(), scratch.len(), ip) };
+        let converted = end as usize - scratch.as_ptr() as usize;
+        data[..converted].copy_from_slice(&scratch[..converted]);
+        converted
+    }
+}
+
+/// Streaming [`BranchFilter`] conversion over data split into arbitrary chunks.
+///
+/// Instructions may straddle chunk boundaries, so the bytes after the last complete
+/// instruction are held back until more data arrives, or [`BranchConverter::finish`] is called.
+/// The output is identical to converting the whole stream at once.
+#[derive(Debug, Clone)]
+pub struct BranchConverter {
+    filter: BranchFilter,
+    encoding: bool,
+    ip: u32,
+    x86_state: u32,
+    pending: Vec<u8>,
+}
+
+impl BranchConverter {
+    /// Creates a converter preparing data for compression, starting at address `ip`.
+    pub fn encoder(filter: BranchFilter, ip: u32) -> Self {
+        Self::new(filter, ip, true)
+    }
+
+    /// Creates a converter reverting [`BranchConverter::encoder`], starting at address `ip`.
+    pub fn decoder(filter: BranchFilter, ip: u32) -> Self {
+        Self::new(filter, ip, false)
+    }
+
+    fn new(filter: BranchFilter, ip: u32, encoding: bool) -> Self {
+        Self {
+            filter,
+            encoding,
+            ip,
+            x86_state: Z7_BRANCH_CONV_ST_X86_STATE_INIT_VAL,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Converts the next chunk of the stream, appending all data which is ready to `output`.
+    pub fn update(&mut self, input: &[u8], output: &mut Vec<u8>) {
+        self.pending.extend_from_slice(input);
+        let converted = self
+            .filter
+            .convert(&mut self.pending, self.ip, &mut self.x86_state, self.encoding);
+
+        self.ip = self.ip.wrapping_add(converted as u32);
+        output.extend(self.pending.drain(..converted));
+    }
+
+    /// Ends the stream, appending the held back trailing bytes to `output` unchanged.
+    pub fn finish(mut self, output: &mut Vec<u8>) {
+        output.append(&mut self.pending);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTERS: [BranchFilter; 8] = [
+        BranchFilter::X86,
+        BranchFilter::PowerPc,
+        BranchFilter::Ia64,
+        BranchFilter::Arm,
+        BranchFilter::ArmThumb,
+        BranchFilter::Sparc,
+        BranchFilter::Arm64,
+        BranchFilter::RiscV,
+    ];
+
+    /// Pseudo-random bytes, dense enough in opcodes for every filter to convert something.
+    fn code() -> Vec<u8> {
+        let mut seed = 0x1234_5678u32;
+        (0..8192)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (seed >> 16) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_branch_filter_round_trip() {
+        let input = code();
+        for filter in FILTERS {
+            let mut data = input.clone();
+            let converted = filter.encode(&mut data, 0x1000);
+            assert!(converted > input.len() - 32, "{filter:?}");
+            assert_ne!(data, input, "{filter:?}");
+
+            assert_eq!(filter.decode(&mut data, 0x1000), converted);
+            assert_eq!(data, input, "{filter:?}");
+        }
+    }
+
+    #[test]
+    fn test_branch_filter_known_values() {
+        // x86 `call +0` at address 0 becomes `call 5`, the absolute target.
+        let mut x86 = [0xE8, 0, 0, 0, 0, 0x90, 0x90, 0x90, 0x90];
+        BranchFilter::X86.encode(&mut x86, 0);
+        assert_eq!(x86[..5], [0xE8, 5, 0, 0, 0]);
+
+        // ARM `bl +0` targets pc + 8, stored in words.
+        let mut arm = [0, 0, 0, 0xEB];
+        assert_eq!(BranchFilter::Arm.encode(&mut arm, 0), 4);
+        assert_eq!(arm, [2, 0, 0, 0xEB]);
+    }
+
+    #[test]
+    fn test_branch_filter_unaligned_slice() {
+        let input = code();
+        for filter in FILTERS {
+            let mut expected = input.clone();
+            let converted = filter.encode(&mut expected, 0);
+
+            let mut buffer = vec![0u8; input.len() + 1];
+            buffer[1..].copy_from_slice(&input);
+            assert_eq!(filter.encode(&mut buffer[1..], 0), converted);
+            assert_eq!(buffer[1..], expected[..], "{filter:?}");
+        }
+    }
+
+    #[test]
+    fn test_branch_converter_chunked() {
+        let input = code();
+        for filter in FILTERS {
+            let mut expected = input.clone();
+            filter.encode(&mut expected, 0);
+
+            let mut encoder = BranchConverter::encoder(filter, 0);
+            let mut encoded = Vec::new();
+            for chunk in input.chunks(333) {
+                encoder.update(chunk, &mut encoded);
+            }
+            encoder.finish(&mut encoded);
+            assert_eq!(encoded, expected, "{filter:?}");
+
+            let mut decoder = BranchConverter::decoder(filter, 0);
+            let mut decoded = Vec::new();
+            for chunk in encoded.chunks(7) {
+                decoder.update(chunk, &mut decoded);
+            }
+            decoder.finish(&mut decoded);
+            assert_eq!(decoded, input, "{filter:?}");
+        }
+    }
+}
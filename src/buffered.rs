@@ -0,0 +1,158 @@
+use std::io::{self, BufRead, Read};
+
+/// Size of the buffer used for reading compressed data from the inner reader.
+const INPUT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Size of the buffer holding decompressed data for [`BufRead`].
+const OUTPUT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Result of a single [`StepDecoder::step`] call.
+pub(crate) struct Step {
+    /// Number of bytes consumed from the input buffer.
+    pub consumed: usize,
+    /// Number of bytes written to the output buffer.
+    pub produced: usize,
+    /// True if the end of the stream was reached and no more output follows.
+    pub finished: bool,
+}
+
+/// A decoder which can be driven by [`BufferedDecoder`].
+pub(crate) trait StepDecoder {
+    /// Name of the format, used in the error for streams that end too early.
+    const FORMAT: &'static str;
+
+    /// Decodes as much of `input` into `output` as possible.
+    ///
+    /// `input_eof` is true if `input` holds the rest of the data. Returning no progress
+    /// without `finished` makes [`BufferedDecoder`] read more input, or fail at the end of it.
+    fn step(&mut self, input: &[u8], output: &mut [u8], input_eof: bool) -> io::Result<Step>;
+}
+
+/// Drives a [`StepDecoder`] with data pulled from an inner reader, implementing [`Read`] and [`BufRead`].
+pub(crate) struct BufferedDecoder<R, D> {
+    inner: R,
+    decoder: D,
+    input: Box<[u8]>,
+    input_pos: usize,
+    input_len: usize,
+    input_eof: bool,
+    output: Box<[u8]>,
+    output_pos: usize,
+    output_len: usize,
+    finished: bool,
+}
+
+impl<R: Read, D: StepDecoder> BufferedDecoder<R, D> {
+    pub(crate) fn new(inner: R, decoder: D) -> Self {
+        Self {
+            inner,
+            decoder,
+            input: vec![0u8; INPUT_BUFFER_SIZE].into_boxed_slice(),
+            input_pos: 0,
+            input_len: 0,
+            input_eof: false,
+            output: vec![0u8; OUTPUT_BUFFER_SIZE].into_boxed_slice(),
+            output_pos: 0,
+            output_len: 0,
+            finished: false,
+        }
+    }
+
+    pub(crate) fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read, D: StepDecoder> BufRead for BufferedDecoder<R, D> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let mut stalled = false;
+        while self.output_pos == self.output_len && !self.finished {
+            if (self.input_pos == self.input_len || stalled) && !self.input_eof {
+                // Keep the unconsumed input, the decoder needs more of it to make progress.
+                self.input.copy_within(self.input_pos..self.input_len, 0);
+                self.input_len -= self.input_pos;
+                self.input_pos = 0;
+                if self.input_len == self.input.len() {
+                    // Reading into an empty slice would look like the end of the input.
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{} decoder made no progress on a full input buffer", D::FORMAT),
+                    ));
+                }
+                let read = self.inner.read(&mut self.input[self.input_len..])?;
+                self.input_len += read;
+                self.input_eof = read == 0;
+            }
+
+            let step = self.decoder.step(
+                &self.input[self.input_pos..self.input_len],
+                &mut self.output,
+                self.input_eof,
+            )?;
+
+            self.input_pos += step.consumed;
+            self.output_pos = 0;
+            self.output_len = step.produced;
+            stalled = step.consumed == 0 && step.produced == 0;
+
+            if step.finished {
+                self.finished = true;
+            } else if stalled && self.input_eof {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("{} stream ended unexpectedly", D::FORMAT),
+                ));
+            }
+        }
+
+        Ok(&self.output[self.output_pos..self.output_len])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.output_pos = (self.output_pos + amt).min(self.output_len);
+    }
+}
+
+impl<R: Read, D: StepDecoder> Read for BufferedDecoder<R, D> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        self.consume(count);
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Never consumes or produces anything, like a decoder waiting for more input than fits.
+    struct StuckDecoder;
+
+    impl StepDecoder for StuckDecoder {
+        const FORMAT: &'static str = "Stuck";
+
+        fn step(&mut self, _input: &[u8], _output: &mut [u8], _input_eof: bool) -> io::Result<Step> {
+            Ok(Step {
+                consumed: 0,
+                produced: 0,
+                finished: false,
+            })
+        }
+    }
+
+    #[test]
+    fn test_full_input_buffer_is_not_eof() {
+        let input = vec![0u8; 2 * INPUT_BUFFER_SIZE];
+        let mut reader = BufferedDecoder::new(Cursor::new(input), StuckDecoder);
+
+        let err = reader.read(&mut [0u8; 16]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::once::InitOnce;
+use crate::*;
+use core::ffi::c_void;
+
+static TABLES: InitOnce = InitOnce::new();
+
+/// Generates the CRC32 and CRC64 lookup tables used by the SDK, exactly once.
+///
+/// Must be called before any SDK code which computes checksums (e.g. the XZ coders) runs.
+pub(crate) fn init_tables() {
+    TABLES.call(|| unsafe {
+        CrcGenerateTable();
+        Crc64GenerateTable();
+    });
+}
+
+const CRC32_INIT: u32 = 0xFFFF_FFFF;
+const CRC64_INIT: u64 = 0xFFFF_FFFF_FFFF_FFFF;
+
+/// Streaming CRC-32 (IEEE, as in `.7z`, `.xz` and zlib) over `7zCrc.c`.
+///
+/// Uses the sliced tables or the hardware CRC instructions selected by `CrcGenerateTable`.
+#[derive(Debug, Clone)]
+pub struct Crc32 {
+    crc: u32,
+}
+
+impl Crc32 {
+    /// Creates a hasher for a new message.
+    pub fn new() -> Self {
+        init_tables();
+        Self { crc: CRC32_INIT }
+    }
+
+    /// Returns the CRC-32 of `data` (`CrcCalc`).
+    pub fn checksum(data: &[u8]) -> u32 {
+        init_tables();
+        unsafe { CrcCalc(data.as_ptr() as *const c_void, data.len()) }
+    }
+
+    /// Hashes the next part of the message.
+    pub fn update(&mut self, data: &[u8]) {
+        self.crc = unsafe { CrcUpdate(self.crc, data.as_ptr() as *const c_void, data.len()) };
+    }
+
+    /// Returns the CRC-32 of the message so far. More data may still be added.
+    pub fn finalize(&self) -> u32 {
+        self.crc ^ CRC32_INIT
+    }
+
+    /// Discards the hashed data.
+    pub fn reset(&mut self) {
+        self.crc = CRC32_INIT;
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Streaming CRC-64 (ECMA-182, as in `.xz`) over `XzCrc64.c`.
+#[derive(Debug, Clone)]
+pub struct Crc64 {
+    crc: u64,
+}
+
+impl Crc64 {
+    /// Creates a hasher for a new message.
+    pub fn new() -> Self {
+        init_tables();
+        Self { crc: CRC64_INIT }
+    }
+
+    /// Returns the CRC-64 of `data` (`Crc64Calc`).
+    pub fn checksum(data: &[u8]) -> u64 {
+        init_tables();
+        unsafe { Crc64Calc(data.as_ptr() as *const c_void, data.len()) }
+    }
+
+    /// Hashes the next part of the message.
+    pub fn update(&mut self, data: &[u8]) {
+        self.crc = unsafe { Crc64Update(self.crc, data.as_ptr() as *const c_void, data.len()) };
+    }
+
+    /// Returns the CRC-64 of the message so far. More data may still be added.
+    pub fn finalize(&self) -> u64 {
+        self.crc ^ CRC64_INIT
+    }
+
+    /// Discards the hashed data.
+    pub fn reset(&mut self) {
+        self.crc = CRC64_INIT;
+    }
+}
+
+impl Default for Crc64 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::io::Write for Crc32 {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::io::Write for Crc64 {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHECK: &[u8] = b"123456789";
+    const FOX: &[u8] = b"The quick brown fox jumps over the lazy dog";
+
+    #[test]
+    fn test_crc32_known_values() {
+        assert_eq!(Crc32::checksum(b""), 0);
+        assert_eq!(Crc32::checksum(CHECK), 0xCBF4_3926);
+        assert_eq!(Crc32::checksum(FOX), 0x414F_A339);
+
+        let mut crc = Crc32::new();
+        for chunk in FOX.chunks(5) {
+            crc.update(chunk);
+        }
+        assert_eq!(crc.finalize(), 0x414F_A339);
+
+        crc.reset();
+        crc.update(CHECK);
+        assert_eq!(crc.finalize(), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_crc64_known_values() {
+        assert_eq!(Crc64::checksum(b""), 0);
+        assert_eq!(Crc64::checksum(CHECK), 0x995D_C9BB_DF19_39FA);
+        assert_eq!(Crc64::checksum(FOX), 0x5B5E_B8C2_E54A_A1C4);
+
+        let mut crc = Crc64::new();
+        for chunk in FOX.chunks(5) {
+            crc.update(chunk);
+        }
+        assert_eq!(crc.finalize(), 0x5B5E_B8C2_E54A_A1C4);
+
+        crc.reset();
+        crc.update(CHECK);
+        assert_eq!(crc.finalize(), 0x995D_C9BB_DF19_39FA);
+    }
+
+    #[test]
+    fn test_crc_streaming_matches_one_shot() {
+        // Long enough for the sliced and hardware paths, with unaligned part boundaries.
+        let data: Vec<u8> = (0..100_000u32).map(|x| (x.wrapping_mul(2_654_435_761) >> 24) as u8).collect();
+        let mut crc32 = Crc32::new();
+        let mut crc64 = Crc64::new();
+        for chunk in data.chunks(1021) {
+            crc32.update(chunk);
+            crc64.update(chunk);
+        }
+        assert_eq!(crc32.finalize(), Crc32::checksum(&data));
+        assert_eq!(crc64.finalize(), Crc64::checksum(&data));
+    }
+}
@@ -0,0 +1,154 @@
+use crate::*;
+
+/// Result of a single [`LzmaDecoder::decode`] call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeProgress {
+    /// Number of bytes consumed from the input buffer.
+    pub consumed: usize,
+    /// Number of bytes written to the output buffer.
+    pub produced: usize,
+    /// Decoder status after this call.
+    pub status: ELzmaStatus,
+}
+
+impl DecodeProgress {
+    /// Returns true if the decoder has reached the end of the stream (end marker found).
+    pub fn is_finished(&self) -> bool {
+        self.status == ELzmaStatus::LZMA_STATUS_FINISHED_WITH_MARK
+    }
+}
+
+/// Safe incremental LZMA decoder over `CLzmaDec`.
+///
+/// Unlike the one-shot `LzmaDecode`, this does not need to know the decompressed size
+/// up front; data can be fed and drained chunk by chunk via [`LzmaDecoder::decode`].
+pub struct LzmaDecoder {
+    state: CLzmaDec,
+    alloc: Allocator,
+}
+
+// The decoder state is exclusively owned and only ever accessed through `&mut self`.
+unsafe impl Send for LzmaDecoder {}
+
+impl LzmaDecoder {
+    /// Creates a new decoder from the 5-byte LZMA properties header.
+    ///
+    /// # Arguments
+    /// * `props` - The properties written by the encoder (e.g. [`LzmaEncoder::write_properties`]).
+    ///
+    /// # Returns
+    /// * `Result<Self, LzmaError>` - The decoder, or [`LzmaError::Unsupported`] for invalid properties
+    ///   and [`LzmaError::Mem`] if the dictionary could not be allocated.
+    pub fn new(props: &[u8]) -> Result<Self, LzmaError> {
+        let mut decoder = Self {
+            state: CLzmaDec::default(),
+            alloc: Allocator::default(),
+        };
+
+        check(unsafe {
+            LzmaDec_Allocate(
+                &mut decoder.state,
+                props.as_ptr(),
+                props.len() as u32,
+                decoder.alloc.as_ref(),
+            )
+        })?;
+
+        decoder.reset();
+        Ok(decoder)
+    }
+
+    /// Creates a new decoder from parsed [`LzmaProperties`].
+    pub fn with_properties(props: &LzmaProperties) -> Result<Self, LzmaError> {
+        Self::new(&props.to_bytes())
+    }
+
+    /// Resets the decoder so a new stream with the same properties can be decoded.
+    pub fn reset(&mut self) {
+        unsafe { LzmaDec_Init(&mut self.state) };
+    }
+
+    /// Decodes as much of `input` into `output` as possible.
+    ///
+    /// Call repeatedly, advancing `input` by [`DecodeProgress::consumed`], until the
+    /// returned status indicates the stream is finished or no more input is available.
+    ///
+    /// # Arguments
+    /// * `input` - Compressed data to consume.
+    /// * `output` - Buffer receiving decompressed data.
+    /// * `finish_mode` - Use `LZMA_FINISH_END` if `output` should end exactly at the end of the stream.
+    pub fn decode(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        finish_mode: ELzmaFinishMode,
+    ) -> Result<DecodeProgress, LzmaError> {
+        let mut consumed = input.len() as SizeT;
+        let mut produced = output.len() as SizeT;
+        let mut status = ELzmaStatus::LZMA_STATUS_NOT_SPECIFIED;
+
+        check(unsafe {
+            LzmaDec_DecodeToBuf(
+                &mut self.state,
+                output.as_mut_ptr(),
+                &mut produced,
+                input.as_ptr(),
+                &mut consumed,
+                finish_mode,
+                &mut status,
+            )
+        })?;
+
+        Ok(DecodeProgress { consumed, produced, status })
+    }
+}
+
+impl Drop for LzmaDecoder {
+    fn drop(&mut self) {
+        unsafe { LzmaDec_Free(&mut self.state, self.alloc.as_ref()) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decoder_chunked_round_trip() {
+        let input: Vec<u8> = (0..20_000u32).map(|x| (x % 251) as u8 ^ (x / 97) as u8).collect();
+
+        // Compress with an end marker, so the decoder can find the end without knowing the size.
+        let mut encoder = LzmaEncoder::new().unwrap();
+        let props = encoder.write_properties().unwrap();
+        let mut compressed = vec![0u8; input.len() * 2];
+        let compressed_size = encoder.encode_slice(&input, &mut compressed, true).unwrap();
+        compressed.truncate(compressed_size);
+
+        // Decompress in small chunks.
+        let mut decoder = LzmaDecoder::new(&props).unwrap();
+        let mut output = Vec::new();
+        let mut chunk = [0u8; 333];
+        let mut remaining = &compressed[..];
+        loop {
+            let feed = &remaining[..remaining.len().min(100)];
+            let progress = decoder
+                .decode(feed, &mut chunk, ELzmaFinishMode::LZMA_FINISH_ANY)
+                .unwrap();
+            remaining = &remaining[progress.consumed..];
+            output.extend_from_slice(&chunk[..progress.produced]);
+            if progress.is_finished() {
+                break;
+            }
+            assert!(progress.consumed > 0 || progress.produced > 0, "decoder stalled");
+        }
+
+        assert!(remaining.is_empty());
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn test_decoder_rejects_bad_props() {
+        assert_eq!(LzmaDecoder::new(&[0u8; 2]).err(), Some(LzmaError::Unsupported));
+        assert_eq!(LzmaDecoder::new(&[0xFF, 0, 0, 1, 0]).err(), Some(LzmaError::Unsupported));
+    }
+}
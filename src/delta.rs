@@ -0,0 +1,98 @@
+use crate::*;
+
+/// Largest distance supported by [`DeltaFilter`].
+pub const DELTA_DISTANCE_MAX: u32 = DELTA_STATE_SIZE;
+
+/// Delta filter from `Delta.c`, storing each byte as the difference to the byte `distance` bytes earlier.
+///
+/// Improves compression of fixed-size samples, such as 16-bit stereo audio (distance 4)
+/// or vertex buffers (the vertex stride). The state carries the last `distance` bytes,
+/// so a stream may be converted in successive buffers; use one filter per direction.
+#[derive(Debug, Clone)]
+pub struct DeltaFilter {
+    distance: u32,
+    state: [u8; DELTA_STATE_SIZE as usize],
+}
+
+impl DeltaFilter {
+    /// Creates a filter for the given distance in bytes.
+    ///
+    /// # Returns
+    /// * `Result<Self, LzmaError>` - The filter, or [`LzmaError::Param`] if `distance` is
+    ///   outside `1..=DELTA_DISTANCE_MAX`.
+    pub fn new(distance: u32) -> Result<Self, LzmaError> {
+        if !(1..=DELTA_DISTANCE_MAX).contains(&distance) {
+            return Err(LzmaError::Param);
+        }
+
+        let mut filter = Self {
+            distance,
+            state: [0; DELTA_STATE_SIZE as usize],
+        };
+        filter.reset();
+        Ok(filter)
+    }
+
+    /// Returns the distance in bytes.
+    pub fn distance(&self) -> u32 {
+        self.distance
+    }
+
+    /// Resets the state, for reuse on a new stream.
+    pub fn reset(&mut self) {
+        unsafe { Delta_Init(self.state.as_mut_ptr()) };
+    }
+
+    /// Converts the next buffer of the stream in place for compression.
+    pub fn encode(&mut self, data: &mut [u8]) {
+        unsafe { Delta_Encode(self.state.as_mut_ptr(), self.distance, data.as_mut_ptr(), data.len()) };
+    }
+
+    /// Reverts [`DeltaFilter::encode`] on the next buffer of the stream, in place.
+    pub fn decode(&mut self, data: &mut [u8]) {
+        unsafe { Delta_Decode(self.state.as_mut_ptr(), self.distance, data.as_mut_ptr(), data.len()) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delta_known_values() {
+        let mut filter = DeltaFilter::new(1).unwrap();
+        let mut data = [1u8, 2, 3, 5, 8];
+        filter.encode(&mut data);
+        assert_eq!(data, [1, 1, 1, 2, 3]);
+
+        // The state continues across buffers.
+        let mut next = [13u8];
+        filter.encode(&mut next);
+        assert_eq!(next, [5]);
+
+        assert_eq!(DeltaFilter::new(0).err(), Some(LzmaError::Param));
+        assert_eq!(DeltaFilter::new(DELTA_DISTANCE_MAX + 1).err(), Some(LzmaError::Param));
+    }
+
+    #[test]
+    fn test_delta_chunked_round_trip() {
+        let input: Vec<u8> = (0..10_000u32).map(|x| (x * 31 % 257) as u8).collect();
+        for distance in [1, 4, 7, DELTA_DISTANCE_MAX] {
+            let mut whole = input.clone();
+            DeltaFilter::new(distance).unwrap().encode(&mut whole);
+
+            let mut encoder = DeltaFilter::new(distance).unwrap();
+            let mut decoder = DeltaFilter::new(distance).unwrap();
+            let mut data = input.clone();
+            for chunk in data.chunks_mut(333) {
+                encoder.encode(chunk);
+            }
+            assert_eq!(data, whole);
+
+            for chunk in data.chunks_mut(100) {
+                decoder.decode(chunk);
+            }
+            assert_eq!(data, input, "distance {distance}");
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use crate::progress::{check_progress, ProgressBridge, ProgressFn};
+use crate::*;
+use alloc::boxed::Box;
+use core::ops::ControlFlow;
+use core::ptr;
+#[cfg(feature = "std")]
+use crate::streams::{SeqInStream, SeqOutStream};
+
+/// Safe owning handle around an LZMA encoder (`CLzmaEncHandle`).
+///
+/// The encoder is created with [`LzmaEncoder::new`] and destroyed automatically
+/// when dropped, so early returns can no longer leak the native handle.
+pub struct LzmaEncoder {
+    handle: CLzmaEncHandle,
+    alloc: Allocator,
+    progress: Option<Box<ProgressFn>>,
+}
+
+// The handle is exclusively owned and only ever accessed through `&mut self`.
+unsafe impl Send for LzmaEncoder {}
+
+impl LzmaEncoder {
+    /// Creates a new encoder with default properties.
+    ///
+    /// # Returns
+    /// * `Result<Self, LzmaError>` - The encoder, or [`LzmaError::Mem`] if allocation failed.
+    pub fn new() -> Result<Self, LzmaError> {
+        let alloc = Allocator::default();
+        let handle = unsafe { LzmaEnc_Create(alloc.as_ref()) };
+        if handle.is_null() {
+            return Err(LzmaError::Mem);
+        }
+
+        Ok(Self {
+            handle,
+            alloc,
+            progress: None,
+        })
+    }
+
+    /// Creates a new encoder and applies the given properties.
+    pub fn with_props(props: &CLzmaEncProps) -> Result<Self, LzmaError> {
+        let mut encoder = Self::new()?;
+        encoder.set_props(props)?;
+        Ok(encoder)
+    }
+
+    /// Creates a new encoder from validated [`LzmaEncoderOptions`].
+    pub fn with_options(options: &LzmaEncoderOptions) -> Result<Self, LzmaError> {
+        Self::with_props(&options.build()?)
+    }
+
+    /// Applies encoder properties (level, dictionary size, lc/lp/pb, etc.).
+    ///
+    /// Start from [`LzmaEncProps_Init`] and modify the fields you need.
+    pub fn set_props(&mut self, props: &CLzmaEncProps) -> Result<(), LzmaError> {
+        check(unsafe { LzmaEnc_SetProps(self.handle, props) })
+    }
+
+    /// Sets a callback receiving `(in_size, out_size)` as compression progresses.
+    /// Returning [`ControlFlow::Break`] cancels the operation with [`LzmaError::Cancelled`].
+    pub fn set_progress<F: FnMut(u64, u64) -> ControlFlow<()> + Send + 'static>(&mut self, progress: F) {
+        self.progress = Some(Box::new(progress));
+    }
+
+    /// Writes the 5-byte LZMA properties header for the current encoder settings.
+    ///
+    /// These bytes are required by the decoder and must be stored alongside the compressed data.
+    pub fn write_properties(&self) -> Result<[u8; LZMA_PROPS_SIZE as usize], LzmaError> {
+        let mut props = [0u8; LZMA_PROPS_SIZE as usize];
+        let mut props_size = props.len() as SizeT;
+        check(unsafe { LzmaEnc_WriteProperties(self.handle, props.as_mut_ptr(), &mut props_size) })?;
+        Ok(props)
+    }
+
+    /// Returns the decoded form of [`LzmaEncoder::write_properties`].
+    pub fn properties(&self) -> Result<LzmaProperties, LzmaError> {
+        LzmaProperties::parse(&self.write_properties()?)
+    }
+
+    /// Compresses `input` into `output` in a single call.
+    ///
+    /// # Arguments
+    /// * `input` - Data to compress.
+    /// * `output` - Buffer receiving the raw LZMA stream (without the properties header).
+    /// * `write_end_mark` - Whether to terminate the stream with an end marker.
+    ///
+    /// # Returns
+    /// * `Result<usize, LzmaError>` - Number of bytes written to `output`.
+    ///   Fails with [`LzmaError::OutputEof`] if `output` is too small to hold the compressed data.
+    pub fn encode_slice(&mut self, input: &[u8], output: &mut [u8], write_end_mark: bool) -> Result<usize, LzmaError> {
+        let mut output_size = output.len() as SizeT;
+        let mut progress = self.progress.as_deref_mut().map(ProgressBridge::new);
+        let progress_ptr = progress.as_mut().map_or(ptr::null(), |p| p.as_ptr());
+        let res = unsafe {
+            LzmaEnc_MemEncode(
+                self.handle,
+                output.as_mut_ptr(),
+                &mut output_size,
+                input.as_ptr(),
+                input.len() as SizeT,
+                write_end_mark as i32,
+                progress_ptr,
+                self.alloc.as_ref(),
+                self.alloc.as_ref(),
+            )
+        };
+        check_progress(progress.as_mut(), res)?;
+        Ok(output_size)
+    }
+
+    /// Like [`LzmaEncoder::encode_slice`], but runs `input` through `delta` first.
+    ///
+    /// The decoder has to apply [`DeltaFilter::decode`] with the same distance to the
+    /// decompressed data. `delta` keeps its state, so successive slices form one stream.
+    /// If encoding fails, `delta` is left unchanged, so the slice can be encoded again.
+    pub fn encode_slice_with_delta(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        write_end_mark: bool,
+        delta: &mut DeltaFilter,
+    ) -> Result<usize, LzmaError> {
+        let mut filter = delta.clone();
+        let mut filtered = input.to_vec();
+        filter.encode(&mut filtered);
+        let size = self.encode_slice(&filtered, output, write_end_mark)?;
+        *delta = filter;
+        Ok(size)
+    }
+
+    /// Compresses everything from `input` into `output` using `LzmaEnc_Encode`.
+    ///
+    /// Only the raw LZMA stream is written, use [`LzmaEncoder::write_properties`] for the header.
+    /// Data is streamed through the encoder, so neither side needs to fit in memory.
+    #[cfg(feature = "std")]
+    pub fn encode_stream<R: std::io::Read, W: std::io::Write>(
+        &mut self,
+        input: &mut R,
+        output: &mut W,
+    ) -> std::io::Result<()> {
+        let mut in_stream = SeqInStream::new(input);
+        let mut out_stream = SeqOutStream::new(output);
+        let mut progress = self.progress.as_deref_mut().map(ProgressBridge::new);
+        let progress_ptr = progress.as_mut().map_or(ptr::null(), |p| p.as_ptr());
+        let res = unsafe {
+            LzmaEnc_Encode(
+                self.handle,
+                out_stream.as_ptr(),
+                in_stream.as_ptr(),
+                progress_ptr,
+                self.alloc.as_ref(),
+                self.alloc.as_ref(),
+            )
+        };
+
+        if let Some(e) = in_stream.take_error().or(out_stream.take_error()) {
+            return Err(e);
+        }
+        check_progress(progress.as_mut(), res)?;
+        Ok(())
+    }
+
+    /// Returns the raw encoder handle, for use with APIs not covered by this wrapper.
+    pub fn as_ptr(&self) -> CLzmaEncHandle {
+        self.handle
+    }
+}
+
+impl Drop for LzmaEncoder {
+    fn drop(&mut self) {
+        unsafe { LzmaEnc_Destroy(self.handle, self.alloc.as_ref(), self.alloc.as_ref()) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_encoder_round_trip() {
+        let input = b"Hello LZMA compression! Hello LZMA compression!";
+
+        let mut props = CLzmaEncProps::default();
+        unsafe { LzmaEncProps_Init(&mut props) };
+
+        let mut encoder = LzmaEncoder::with_props(&props).unwrap();
+        let header = encoder.write_properties().unwrap();
+
+        let mut compressed = vec![0u8; input.len() * 2 + 64];
+        let compressed_size = encoder.encode_slice(input, &mut compressed, false).unwrap();
+        compressed.truncate(compressed_size);
+
+        let alloc = Allocator::default();
+        let mut dest = vec![0u8; input.len()];
+        let mut dest_size = dest.len() as SizeT;
+        let mut source_len = compressed.len() as SizeT;
+        let mut status = ELzmaStatus::LZMA_STATUS_NOT_SPECIFIED;
+        let res = unsafe {
+            LzmaDecode(
+                dest.as_mut_ptr(),
+                &mut dest_size,
+                compressed.as_ptr(),
+                &mut source_len,
+                header.as_ptr(),
+                header.len() as u32,
+                ELzmaFinishMode::LZMA_FINISH_END,
+                &mut status,
+                alloc.as_ref(),
+            )
+        };
+        assert_eq!(res, SZ_OK as i32);
+        assert_eq!(&dest[..dest_size], input);
+    }
+
+    #[test]
+    fn test_encoder_output_too_small() {
+        let input = [0x55u8; 4096];
+        let mut encoder = LzmaEncoder::new().unwrap();
+        let mut compressed = [0u8; 4];
+        let res = encoder.encode_slice(&input, &mut compressed, false);
+        assert_eq!(res, Err(LzmaError::OutputEof));
+    }
+
+    #[test]
+    fn test_encoder_delta_round_trip() {
+        let input: Vec<u8> = (0..1 << 16u32).flat_map(|x| ((x * 3) as u16).to_le_bytes()).collect();
+        let (first, second) = input.split_at(input.len() / 2);
+        let mut encoder = LzmaEncoder::new().unwrap();
+        let props = encoder.write_properties().unwrap();
+        let mut delta = DeltaFilter::new(2).unwrap();
+
+        // Each slice is a separate LZMA stream with an end marker, but one delta stream.
+        let decode = |compressed: &[u8], size: usize| {
+            let mut output = vec![0u8; size + 1];
+            let progress = LzmaDecoder::new(&props)
+                .unwrap()
+                .decode(compressed, &mut output, ELzmaFinishMode::LZMA_FINISH_ANY)
+                .unwrap();
+            assert!(progress.is_finished());
+            output.truncate(progress.produced);
+            output
+        };
+
+        let mut compressed = vec![0u8; input.len()];
+        let size = encoder.encode_slice_with_delta(first, &mut compressed, true, &mut delta).unwrap();
+        let mut decoded = decode(&compressed[..size], first.len());
+
+        // A failed call must not advance the filter state.
+        let res = encoder.encode_slice_with_delta(second, &mut [0u8; 4], true, &mut delta);
+        assert_eq!(res, Err(LzmaError::OutputEof));
+
+        let size = encoder.encode_slice_with_delta(second, &mut compressed, true, &mut delta).unwrap();
+        decoded.extend_from_slice(&decode(&compressed[..size], second.len()));
+
+        DeltaFilter::new(2).unwrap().decode(&mut decoded);
+        assert_eq!(decoded, input);
+    }
+
+    #[test]
+    fn test_encoder_progress_cancel() {
+        let input: Vec<u8> = (0..4u32 << 20).map(|x| (x % 251) as u8 ^ (x >> 13) as u8).collect();
+        let mut compressed = vec![0u8; input.len() + 1024];
+
+        let mut encoder = LzmaEncoder::new().unwrap();
+        let in_sizes = Arc::new(Mutex::new(Vec::new()));
+        let recorded = in_sizes.clone();
+        encoder.set_progress(move |in_size, _| {
+            recorded.lock().unwrap().push(in_size);
+            ControlFlow::Continue(())
+        });
+        assert!(encoder.encode_slice(&input, &mut compressed, false).is_ok());
+        assert!(in_sizes.lock().unwrap().windows(2).all(|pair| pair[0] <= pair[1]));
+
+        let calls = Arc::new(AtomicU32::new(0));
+        let counted = calls.clone();
+        encoder.set_progress(move |_, _| {
+            counted.fetch_add(1, Ordering::Relaxed);
+            ControlFlow::Break(())
+        });
+        let res = encoder.encode_slice(&input, &mut compressed, false);
+        assert_eq!(res, Err(LzmaError::Cancelled));
+        assert_eq!(calls.load(Ordering::Relaxed), 1, "called again after cancelling");
+
+        #[cfg(feature = "std")]
+        {
+            encoder.set_progress(|_, _| ControlFlow::Break(()));
+            let err = encoder.encode_stream(&mut &input[..], &mut Vec::new()).unwrap_err();
+            assert_eq!(err.into_inner().unwrap().downcast_ref(), Some(&LzmaError::Cancelled));
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_encoder_progress_panic() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        let input: Vec<u8> = (0..4u32 << 20).map(|x| (x % 251) as u8 ^ (x >> 13) as u8).collect();
+        let mut compressed = vec![0u8; input.len() + 1024];
+
+        let mut encoder = LzmaEncoder::new().unwrap();
+        encoder.set_progress(|_, _| panic!("progress panicked"));
+        let res = panic::catch_unwind(AssertUnwindSafe(|| encoder.encode_slice(&input, &mut compressed, false)));
+        assert_eq!(res.unwrap_err().downcast_ref::<&str>(), Some(&"progress panicked"));
+
+        // The encoder is still usable afterwards.
+        encoder.set_progress(|_, _| ControlFlow::Continue(()));
+        assert!(encoder.encode_slice(&input, &mut compressed, false).is_ok());
+    }
+}
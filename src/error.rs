@@ -0,0 +1,160 @@
+use crate::*;
+use core::fmt;
+
+/// Typed representation of the `SZ_ERROR_*` result codes returned by LZMA-SDK.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LzmaError {
+    /// Data error, the compressed stream is corrupt (`SZ_ERROR_DATA`).
+    Data,
+    /// Memory allocation failed (`SZ_ERROR_MEM`).
+    Mem,
+    /// Checksum mismatch (`SZ_ERROR_CRC`).
+    Crc,
+    /// Unsupported properties or method (`SZ_ERROR_UNSUPPORTED`).
+    Unsupported,
+    /// Invalid parameter (`SZ_ERROR_PARAM`).
+    Param,
+    /// Input ended before the end of the stream (`SZ_ERROR_INPUT_EOF`).
+    InputEof,
+    /// Output buffer is too small (`SZ_ERROR_OUTPUT_EOF`).
+    OutputEof,
+    /// Reading from an input stream failed (`SZ_ERROR_READ`).
+    Read,
+    /// Writing to an output stream failed (`SZ_ERROR_WRITE`).
+    Write,
+    /// The operation was stopped by a progress callback (`SZ_ERROR_PROGRESS`).
+    ///
+    /// Callbacks set through this crate report [`LzmaError::Cancelled`] instead.
+    Progress,
+    /// The operation was cancelled by a [`ProgressFn`] returning [`ControlFlow::Break`](core::ops::ControlFlow::Break).
+    Cancelled,
+    /// Generic failure (`SZ_ERROR_FAIL`).
+    Fail,
+    /// Thread creation or synchronization failed (`SZ_ERROR_THREAD`).
+    Thread,
+    /// Archive is corrupt (`SZ_ERROR_ARCHIVE`).
+    Archive,
+    /// Input is not an archive of the expected type (`SZ_ERROR_NO_ARCHIVE`).
+    NoArchive,
+    /// A result code not known to this crate.
+    Unknown(i32),
+}
+
+impl LzmaError {
+    /// Returns the raw `SRes` code corresponding to this error.
+    pub fn code(&self) -> SRes {
+        (match self {
+            LzmaError::Data => SZ_ERROR_DATA,
+            LzmaError::Mem => SZ_ERROR_MEM,
+            LzmaError::Crc => SZ_ERROR_CRC,
+            LzmaError::Unsupported => SZ_ERROR_UNSUPPORTED,
+            LzmaError::Param => SZ_ERROR_PARAM,
+            LzmaError::InputEof => SZ_ERROR_INPUT_EOF,
+            LzmaError::OutputEof => SZ_ERROR_OUTPUT_EOF,
+            LzmaError::Read => SZ_ERROR_READ,
+            LzmaError::Write => SZ_ERROR_WRITE,
+            LzmaError::Progress | LzmaError::Cancelled => SZ_ERROR_PROGRESS,
+            LzmaError::Fail => SZ_ERROR_FAIL,
+            LzmaError::Thread => SZ_ERROR_THREAD,
+            LzmaError::Archive => SZ_ERROR_ARCHIVE,
+            LzmaError::NoArchive => SZ_ERROR_NO_ARCHIVE,
+            LzmaError::Unknown(code) => return *code,
+        }) as SRes
+    }
+}
+
+impl From<SRes> for LzmaError {
+    fn from(res: SRes) -> Self {
+        match res as u32 {
+            SZ_ERROR_DATA => LzmaError::Data,
+            SZ_ERROR_MEM => LzmaError::Mem,
+            SZ_ERROR_CRC => LzmaError::Crc,
+            SZ_ERROR_UNSUPPORTED => LzmaError::Unsupported,
+            SZ_ERROR_PARAM => LzmaError::Param,
+            SZ_ERROR_INPUT_EOF => LzmaError::InputEof,
+            SZ_ERROR_OUTPUT_EOF => LzmaError::OutputEof,
+            SZ_ERROR_READ => LzmaError::Read,
+            SZ_ERROR_WRITE => LzmaError::Write,
+            SZ_ERROR_PROGRESS => LzmaError::Progress,
+            SZ_ERROR_FAIL => LzmaError::Fail,
+            SZ_ERROR_THREAD => LzmaError::Thread,
+            SZ_ERROR_ARCHIVE => LzmaError::Archive,
+            SZ_ERROR_NO_ARCHIVE => LzmaError::NoArchive,
+            _ => LzmaError::Unknown(res),
+        }
+    }
+}
+
+impl fmt::Display for LzmaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LzmaError::Data => f.write_str("corrupt compressed data"),
+            LzmaError::Mem => f.write_str("memory allocation failed"),
+            LzmaError::Crc => f.write_str("checksum mismatch"),
+            LzmaError::Unsupported => f.write_str("unsupported properties or method"),
+            LzmaError::Param => f.write_str("invalid parameter"),
+            LzmaError::InputEof => f.write_str("unexpected end of input"),
+            LzmaError::OutputEof => f.write_str("output buffer too small"),
+            LzmaError::Read => f.write_str("failed to read from input stream"),
+            LzmaError::Write => f.write_str("failed to write to output stream"),
+            LzmaError::Progress => f.write_str("operation stopped by progress callback"),
+            LzmaError::Cancelled => f.write_str("operation cancelled"),
+            LzmaError::Fail => f.write_str("operation failed"),
+            LzmaError::Thread => f.write_str("threading error"),
+            LzmaError::Archive => f.write_str("corrupt archive"),
+            LzmaError::NoArchive => f.write_str("not an archive"),
+            LzmaError::Unknown(code) => write!(f, "unknown LZMA-SDK error code {}", code),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LzmaError {}
+
+#[cfg(feature = "std")]
+impl From<LzmaError> for std::io::Error {
+    fn from(err: LzmaError) -> Self {
+        use std::io::ErrorKind;
+        let kind = match err {
+            LzmaError::Data | LzmaError::Crc | LzmaError::Unsupported | LzmaError::Archive | LzmaError::NoArchive => {
+                ErrorKind::InvalidData
+            }
+            LzmaError::InputEof => ErrorKind::UnexpectedEof,
+            LzmaError::OutputEof => ErrorKind::WriteZero,
+            LzmaError::Mem => ErrorKind::OutOfMemory,
+            LzmaError::Param => ErrorKind::InvalidInput,
+            _ => ErrorKind::Other,
+        };
+        std::io::Error::new(kind, err)
+    }
+}
+
+/// Converts an LZMA-SDK result code into a [`Result`].
+///
+/// # Returns
+/// * `Result<(), LzmaError>` - `Ok` for `SZ_OK`, otherwise the corresponding [`LzmaError`].
+#[inline]
+pub fn check(res: SRes) -> Result<(), LzmaError> {
+    if res == SZ_OK as SRes {
+        Ok(())
+    } else {
+        Err(LzmaError::from(res))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_codes_round_trip() {
+        for code in 1..=20 {
+            assert_eq!(LzmaError::from(code).code(), code);
+        }
+        assert_eq!(check(SZ_OK as SRes), Ok(()));
+        assert_eq!(check(SZ_ERROR_CRC as SRes), Err(LzmaError::Crc));
+        assert_eq!(LzmaError::from(13), LzmaError::Unknown(13));
+        assert_eq!(LzmaError::Cancelled.code(), SZ_ERROR_PROGRESS as SRes);
+    }
+}
@@ -1,5 +1,5 @@
 #![doc = include_str!("../README.md")]
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 #![allow(non_upper_case_globals)]
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
@@ -11,6 +11,90 @@ extern crate core;
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
+mod aes;
+mod alone;
+mod bcj2;
+mod branch;
+#[cfg(feature = "std")]
+mod buffered;
+mod crc;
+mod decoder;
+mod delta;
+mod encoder;
+mod error;
+mod lzma2_decoder;
+mod lzma2_encoder;
+#[cfg(feature = "std")]
+mod lzma2_mt_decoder;
+#[cfg(feature = "std")]
+mod lzma2_reader;
+mod once;
+mod options;
+mod ppmd;
+mod progress;
+mod properties;
+#[cfg(feature = "std")]
+mod reader;
+#[cfg(feature = "std")]
+mod seven_zip;
+mod seven_zip_aes;
+mod sha;
+#[cfg(feature = "std")]
+mod streams;
+#[cfg(feature = "std")]
+mod writer;
+mod xz_decoder;
+#[cfg(feature = "std")]
+mod xz_encoder;
+#[cfg(feature = "std")]
+mod xz_mt_decoder;
+#[cfg(feature = "std")]
+mod xz_reader;
+pub use aes::{aes_hardware_accelerated, Aes256Cbc, Aes256Ctr, AES256_KEY_SIZE};
+pub use alone::{compress_lzma_alone, decompress_lzma_alone, LzmaAloneHeader, LZMA_ALONE_HEADER_SIZE};
+pub use bcj2::{Bcj2Decoder, Bcj2Encoder, Bcj2Streams};
+pub use branch::{BranchConverter, BranchFilter};
+pub use crc::{Crc32, Crc64};
+pub use decoder::{DecodeProgress, LzmaDecoder};
+pub use delta::{DeltaFilter, DELTA_DISTANCE_MAX};
+pub use encoder::LzmaEncoder;
+pub use error::{check, LzmaError};
+pub use lzma2_decoder::{lzma2_dict_size, Lzma2Decoder, LZMA2_PROP_MAX};
+pub use lzma2_encoder::{Lzma2Encoder, Lzma2EncoderOptions, LZMA2_BLOCK_SIZE_AUTO, LZMA2_BLOCK_SIZE_SOLID};
+pub use options::{LzmaEncoderOptions, LzmaMode, MatchFinder, LZMA_DICT_SIZE_MAX, LZMA_DICT_SIZE_MIN};
+pub use ppmd::{Ppmd7Decoder, Ppmd7Encoder, PPMD7_PROPS_SIZE};
+pub use progress::ProgressFn;
+pub use properties::LzmaProperties;
+pub use seven_zip_aes::{
+    SevenZipAesKey, SevenZipAesKeyCache, SevenZipAesProperties, SEVEN_ZIP_AES_CYCLES_DEFAULT, SEVEN_ZIP_AES_CYCLES_MAX,
+    SEVEN_ZIP_AES_CYCLES_NO_HASH, SEVEN_ZIP_AES_SALT_MAX,
+};
+pub use sha::{
+    sha1_hardware_accelerated, sha256_hardware_accelerated, Sha1, Sha256, ShaImplementation, SHA1_DIGEST_LEN,
+    SHA256_DIGEST_LEN,
+};
+pub use xz_decoder::{decompress_xz, XzDecodeProgress, XzDecoder};
+#[cfg(feature = "std")]
+pub use lzma2_mt_decoder::{Lzma2MtDecodeStats, Lzma2MtDecoder, Lzma2MtDecoderOptions};
+#[cfg(feature = "std")]
+pub use lzma2_reader::Lzma2Reader;
+#[cfg(feature = "std")]
+pub use reader::LzmaReader;
+#[cfg(feature = "std")]
+pub use seven_zip::{SevenZipArchive, SevenZipEntry};
+#[cfg(feature = "std")]
+pub use seven_zip_aes::{SevenZipAesReader, SevenZipAesWriter};
+#[cfg(feature = "std")]
+pub use streams::{LookInStream, SeekInStream, LOOK_BUFFER_SIZE};
+#[cfg(feature = "std")]
+pub use writer::LzmaWriter;
+#[cfg(feature = "std")]
+pub use xz_encoder::{XzCheck, XzEncoder, XzEncoderOptions, XzFilter};
+#[cfg(feature = "std")]
+pub use xz_mt_decoder::{XzMtDecodeStats, XzMtDecoder, XzMtDecoderOptions};
+#[cfg(feature = "std")]
+pub use xz_reader::XzReader;
+
 // Provide a default allocator implementation for lzma encoder.
 pub struct Allocator {
     alloc: ISzAlloc,
@@ -28,6 +112,7 @@ impl Default for Allocator {
 }
 
 impl Allocator {
+    #[allow(clippy::should_implement_trait)]
     pub fn as_ref(&self) -> &ISzAlloc {
         &self.alloc
     }
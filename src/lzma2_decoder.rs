@@ -0,0 +1,163 @@
+use crate::*;
+
+/// Largest valid LZMA2 properties byte (4 GiB - 1 dictionary).
+pub const LZMA2_PROP_MAX: u8 = 40;
+
+/// Returns the dictionary size encoded in an LZMA2 properties byte, or `None` if it is invalid.
+///
+/// The dictionary size is `(2 | (prop & 1)) << (prop / 2 + 11)`, with `40` meaning `0xFFFFFFFF`.
+pub fn lzma2_dict_size(prop: u8) -> Option<u32> {
+    match prop {
+        LZMA2_PROP_MAX => Some(u32::MAX),
+        0..LZMA2_PROP_MAX => Some((2 | (prop as u32 & 1)) << (prop / 2 + 11)),
+        _ => None,
+    }
+}
+
+/// Safe incremental LZMA2 decoder over `CLzma2Dec`.
+///
+/// LZMA2 streams always end with an end byte, so the decompressed size does not
+/// need to be known; data can be fed and drained chunk by chunk via [`Lzma2Decoder::decode`].
+pub struct Lzma2Decoder {
+    state: CLzma2Dec,
+    alloc: Allocator,
+}
+
+// The decoder state is exclusively owned and only ever accessed through `&mut self`.
+unsafe impl Send for Lzma2Decoder {}
+
+impl Lzma2Decoder {
+    /// Creates a new decoder from the LZMA2 properties byte.
+    ///
+    /// # Arguments
+    /// * `prop` - The dictionary size byte written by the encoder (e.g. [`Lzma2Encoder::write_properties`]).
+    ///
+    /// # Returns
+    /// * `Result<Self, LzmaError>` - The decoder, or [`LzmaError::Unsupported`] if `prop` is above
+    ///   [`LZMA2_PROP_MAX`] and [`LzmaError::Mem`] if the dictionary could not be allocated.
+    pub fn new(prop: u8) -> Result<Self, LzmaError> {
+        let mut decoder = Self {
+            state: CLzma2Dec::default(),
+            alloc: Allocator::default(),
+        };
+
+        check(unsafe { Lzma2Dec_Allocate(&mut decoder.state, prop, decoder.alloc.as_ref()) })?;
+
+        decoder.reset();
+        Ok(decoder)
+    }
+
+    /// Resets the decoder so a new stream with the same properties can be decoded.
+    pub fn reset(&mut self) {
+        unsafe { Lzma2Dec_Init(&mut self.state) };
+    }
+
+    /// Decodes as much of `input` into `output` as possible.
+    ///
+    /// Call repeatedly, advancing `input` by [`DecodeProgress::consumed`], until the
+    /// returned status indicates the stream is finished or no more input is available.
+    ///
+    /// # Arguments
+    /// * `input` - Compressed data to consume.
+    /// * `output` - Buffer receiving decompressed data.
+    /// * `finish_mode` - Use `LZMA_FINISH_END` if `output` should end exactly at the end of the stream.
+    pub fn decode(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        finish_mode: ELzmaFinishMode,
+    ) -> Result<DecodeProgress, LzmaError> {
+        let mut consumed = input.len() as SizeT;
+        let mut produced = output.len() as SizeT;
+        let mut status = ELzmaStatus::LZMA_STATUS_NOT_SPECIFIED;
+
+        check(unsafe {
+            Lzma2Dec_DecodeToBuf(
+                &mut self.state,
+                output.as_mut_ptr(),
+                &mut produced,
+                input.as_ptr(),
+                &mut consumed,
+                finish_mode,
+                &mut status,
+            )
+        })?;
+
+        Ok(DecodeProgress { consumed, produced, status })
+    }
+}
+
+impl Drop for Lzma2Decoder {
+    fn drop(&mut self) {
+        // `Lzma2Dec_Free` is a macro over the inner LZMA decoder.
+        unsafe { LzmaDec_Free(&mut self.state.decoder, self.alloc.as_ref()) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `b"Hello from python lzma FORMAT_RAW LZMA2!\n" * 4`, compressed by liblzma
+    /// as a raw LZMA2 stream with a 1 MiB dictionary (properties byte 16).
+    const XZ_LZMA2_FIXTURE: [u8; 57] = [
+        0xE0, 0x00, 0xA3, 0x00, 0x31, 0x5D, 0x00, 0x24, 0x19, 0x49, 0x98, 0x6F, 0x10, 0x15, 0x88, 0x4C, 0x89,
+        0xFC, 0x9A, 0x7E, 0x68, 0x5B, 0xF5, 0x79, 0x69, 0xE6, 0x72, 0x08, 0x12, 0x4E, 0xDA, 0x49, 0xEB, 0x18,
+        0x82, 0x3C, 0x4F, 0x0C, 0x33, 0x94, 0x3A, 0x92, 0x6C, 0x41, 0x8A, 0x2C, 0xF1, 0xAC, 0x12, 0x82, 0x62,
+        0xA9, 0x9E, 0x53, 0x80, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn test_lzma2_decoder_external_stream() {
+        assert_eq!(lzma2_dict_size(16), Some(1 << 20));
+
+        let mut decoder = Lzma2Decoder::new(16).unwrap();
+        let mut output = [0u8; 256];
+        let progress = decoder
+            .decode(&XZ_LZMA2_FIXTURE, &mut output, ELzmaFinishMode::LZMA_FINISH_ANY)
+            .unwrap();
+        assert!(progress.is_finished());
+        assert_eq!(progress.consumed, XZ_LZMA2_FIXTURE.len());
+        assert_eq!(&output[..progress.produced], b"Hello from python lzma FORMAT_RAW LZMA2!\n".repeat(4));
+    }
+
+    #[test]
+    fn test_lzma2_decoder_chunked_round_trip() {
+        // Small blocks, so the stream contains several dictionary resets.
+        let input: Vec<u8> = (0..300_000u32).map(|x| (x % 251) as u8 ^ (x / 97) as u8).collect();
+        let options = Lzma2EncoderOptions::preset(1).block_size(1 << 16);
+        let mut encoder = Lzma2Encoder::with_options(&options).unwrap();
+        let compressed = encoder.encode_to_vec(&input).unwrap();
+
+        let mut decoder = Lzma2Decoder::new(encoder.write_properties()).unwrap();
+        let mut output = Vec::new();
+        let mut chunk = [0u8; 4099];
+        let mut remaining = &compressed[..];
+        loop {
+            let feed = &remaining[..remaining.len().min(1000)];
+            let progress = decoder
+                .decode(feed, &mut chunk, ELzmaFinishMode::LZMA_FINISH_ANY)
+                .unwrap();
+            remaining = &remaining[progress.consumed..];
+            output.extend_from_slice(&chunk[..progress.produced]);
+            if progress.is_finished() {
+                break;
+            }
+            assert!(progress.consumed > 0 || progress.produced > 0, "decoder stalled");
+        }
+
+        assert!(remaining.is_empty());
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn test_lzma2_decoder_rejects_bad_input() {
+        assert_eq!(lzma2_dict_size(41), None);
+        assert_eq!(Lzma2Decoder::new(41).err(), Some(LzmaError::Unsupported));
+
+        // Control byte 3-0x7F is reserved.
+        let mut decoder = Lzma2Decoder::new(16).unwrap();
+        let result = decoder.decode(&[0x03, 0x00, 0x00], &mut [0u8; 16], ELzmaFinishMode::LZMA_FINISH_ANY);
+        assert_eq!(result, Err(LzmaError::Data));
+    }
+}
@@ -0,0 +1,445 @@
+use crate::progress::{check_progress, ProgressBridge, ProgressFn};
+use crate::*;
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::ControlFlow;
+use core::ptr;
+#[cfg(feature = "std")]
+use crate::streams::{SeqInStream, SeqOutStream};
+
+/// Block size value letting the encoder pick a block size based on the dictionary size.
+pub const LZMA2_BLOCK_SIZE_AUTO: u64 = 0;
+
+/// Block size value encoding the whole input as a single block (no multithreading across blocks).
+pub const LZMA2_BLOCK_SIZE_SOLID: u64 = u64::MAX;
+
+/// Builder for `CLzma2EncProps`.
+///
+/// LZMA2 splits the input into independently compressed blocks, which can be
+/// encoded in parallel by up to `block_threads` threads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lzma2EncoderOptions {
+    lzma: LzmaEncoderOptions,
+    block_size: Option<u64>,
+    block_threads: Option<u32>,
+    total_threads: Option<u32>,
+}
+
+impl Default for Lzma2EncoderOptions {
+    fn default() -> Self {
+        Self::preset(5)
+    }
+}
+
+impl Lzma2EncoderOptions {
+    /// Creates options for the given preset level (0 = fastest, 9 = best compression).
+    pub fn preset(level: u32) -> Self {
+        Self::from_lzma(LzmaEncoderOptions::preset(level))
+    }
+
+    /// Creates options from LZMA encoder options, used for each block.
+    ///
+    /// The end marker setting is ignored, LZMA2 streams always end with an end byte.
+    pub fn from_lzma(lzma: LzmaEncoderOptions) -> Self {
+        Self {
+            lzma,
+            block_size: None,
+            block_threads: None,
+            total_threads: None,
+        }
+    }
+
+    /// Sets the size of independently compressed blocks.
+    ///
+    /// Use [`LZMA2_BLOCK_SIZE_AUTO`] (default) or [`LZMA2_BLOCK_SIZE_SOLID`] for special behaviour.
+    pub fn block_size(mut self, block_size: u64) -> Self {
+        self.block_size = Some(block_size);
+        self
+    }
+
+    /// Sets the maximum number of blocks encoded in parallel (`numBlockThreads_Max`).
+    pub fn block_threads(mut self, block_threads: u32) -> Self {
+        self.block_threads = Some(block_threads);
+        self
+    }
+
+    /// Sets the total number of threads used, including the threads of each LZMA encoder.
+    pub fn total_threads(mut self, total_threads: u32) -> Self {
+        self.total_threads = Some(total_threads);
+        self
+    }
+
+    /// Validates the options and produces normalized encoder properties.
+    ///
+    /// # Returns
+    /// * `Result<CLzma2EncProps, LzmaError>` - The properties with all defaults filled in
+    ///   by `Lzma2EncProps_Normalize`, or [`LzmaError::Param`] if any value is out of range.
+    pub fn build(&self) -> Result<CLzma2EncProps, LzmaError> {
+        if self.block_threads == Some(0) || self.total_threads == Some(0) {
+            return Err(LzmaError::Param);
+        }
+
+        let mut props = CLzma2EncProps::default();
+        unsafe { Lzma2EncProps_Init(&mut props) };
+        props.lzmaProps = self.lzma.build()?;
+        if let Some(block_size) = self.block_size {
+            props.blockSize = block_size;
+        }
+        if let Some(block_threads) = self.block_threads {
+            props.numBlockThreads_Max = block_threads as i32;
+        }
+        if let Some(total_threads) = self.total_threads {
+            props.numTotalThreads = total_threads as i32;
+        }
+
+        unsafe { Lzma2EncProps_Normalize(&mut props) };
+        Ok(props)
+    }
+}
+
+/// Safe owning handle around an LZMA2 encoder (`CLzma2EncHandle`).
+///
+/// Unlike [`LzmaEncoder`], the input is split into blocks which can be compressed
+/// on multiple threads, see [`Lzma2EncoderOptions`].
+pub struct Lzma2Encoder {
+    handle: CLzma2EncHandle,
+    // Never read, but boxed and kept alive since the encoder keeps a pointer to it.
+    _alloc: Box<Allocator>,
+    progress: Option<Box<ProgressFn>>,
+    // The normalized block size of the applied properties, used to bound the output size.
+    block_size: u64,
+}
+
+// The handle is exclusively owned and only ever accessed through `&mut self`.
+unsafe impl Send for Lzma2Encoder {}
+
+impl Lzma2Encoder {
+    /// Creates a new encoder with default properties.
+    ///
+    /// # Returns
+    /// * `Result<Self, LzmaError>` - The encoder, or [`LzmaError::Mem`] if allocation failed.
+    pub fn new() -> Result<Self, LzmaError> {
+        let alloc = Box::new(Allocator::default());
+        let handle = unsafe { Lzma2Enc_Create(alloc.as_ref().as_ref(), alloc.as_ref().as_ref()) };
+        if handle.is_null() {
+            return Err(LzmaError::Mem);
+        }
+
+        // Lzma2Enc_Create starts out with the default properties.
+        let mut props = CLzma2EncProps::default();
+        unsafe { Lzma2EncProps_Init(&mut props) };
+
+        Ok(Self {
+            handle,
+            _alloc: alloc,
+            progress: None,
+            block_size: normalized_block_size(&props),
+        })
+    }
+
+    /// Creates a new encoder and applies the given properties.
+    pub fn with_props(props: &CLzma2EncProps) -> Result<Self, LzmaError> {
+        let mut encoder = Self::new()?;
+        encoder.set_props(props)?;
+        Ok(encoder)
+    }
+
+    /// Creates a new encoder from validated [`Lzma2EncoderOptions`].
+    pub fn with_options(options: &Lzma2EncoderOptions) -> Result<Self, LzmaError> {
+        Self::with_props(&options.build()?)
+    }
+
+    /// Applies encoder properties.
+    pub fn set_props(&mut self, props: &CLzma2EncProps) -> Result<(), LzmaError> {
+        check(unsafe { Lzma2Enc_SetProps(self.handle, props) })?;
+        self.block_size = normalized_block_size(props);
+        Ok(())
+    }
+
+    /// Sets the expected size of the input, which lets the encoder reduce memory usage.
+    pub fn set_data_size(&mut self, size: u64) {
+        unsafe { Lzma2Enc_SetDataSize(self.handle, size) };
+    }
+
+    /// Sets a callback receiving `(in_size, out_size)` as compression progresses.
+    /// Returning [`ControlFlow::Break`] cancels the operation with [`LzmaError::Cancelled`].
+    pub fn set_progress<F: FnMut(u64, u64) -> ControlFlow<()> + Send + 'static>(&mut self, progress: F) {
+        self.progress = Some(Box::new(progress));
+    }
+
+    /// Returns the single LZMA2 properties byte (encoded dictionary size), required by the decoder.
+    pub fn write_properties(&self) -> u8 {
+        unsafe { Lzma2Enc_WriteProperties(self.handle) }
+    }
+
+    /// Compresses `input` into a newly allocated buffer.
+    ///
+    /// Only the raw LZMA2 stream is returned, use [`Lzma2Encoder::write_properties`] for the header.
+    pub fn encode_to_vec(&mut self, input: &[u8]) -> Result<Vec<u8>, LzmaError> {
+        let capacity = max_encoded_size(input.len(), self.block_size);
+        let mut output = vec![0u8; capacity];
+        let mut output_size = capacity;
+
+        self.set_data_size(input.len() as u64);
+        let mut progress = self.progress.as_deref_mut().map(ProgressBridge::new);
+        let progress_ptr = progress.as_mut().map_or(ptr::null(), |p| p.as_ptr());
+        let res = unsafe {
+            Lzma2Enc_Encode2(
+                self.handle,
+                ptr::null(),
+                output.as_mut_ptr(),
+                &mut output_size,
+                ptr::null(),
+                input.as_ptr(),
+                input.len(),
+                progress_ptr,
+            )
+        };
+        // Back to unknown, so the size doesn't leak into later calls of `encode_stream`.
+        unsafe { Lzma2Enc_SetDataSize(self.handle, u64::MAX) };
+        check_progress(progress.as_mut(), res)?;
+
+        output.truncate(output_size);
+        Ok(output)
+    }
+
+    /// Like [`Lzma2Encoder::encode_to_vec`], but runs `input` through `delta` first.
+    ///
+    /// The decoder has to apply [`DeltaFilter::decode`] with the same distance to the
+    /// decompressed data. `delta` keeps its state, so successive calls form one stream.
+    /// If encoding fails, `delta` is left unchanged, so the input can be encoded again.
+    pub fn encode_to_vec_with_delta(&mut self, input: &[u8], delta: &mut DeltaFilter) -> Result<Vec<u8>, LzmaError> {
+        let mut filter = delta.clone();
+        let mut filtered = input.to_vec();
+        filter.encode(&mut filtered);
+        let output = self.encode_to_vec(&filtered)?;
+        *delta = filter;
+        Ok(output)
+    }
+
+    /// Compresses everything from `input` into `output`.
+    ///
+    /// Only the raw LZMA2 stream is written, use [`Lzma2Encoder::write_properties`] for the header.
+    /// When multithreaded, the streams may be accessed from worker threads, hence the `Send` bounds.
+    #[cfg(feature = "std")]
+    pub fn encode_stream<R: std::io::Read + Send, W: std::io::Write + Send>(
+        &mut self,
+        input: &mut R,
+        output: &mut W,
+    ) -> std::io::Result<()> {
+        let mut in_stream = SeqInStream::new(input);
+        let mut out_stream = SeqOutStream::new(output);
+        let mut progress = self.progress.as_deref_mut().map(ProgressBridge::new);
+        let progress_ptr = progress.as_mut().map_or(ptr::null(), |p| p.as_ptr());
+        let res = unsafe {
+            Lzma2Enc_Encode2(
+                self.handle,
+                out_stream.as_ptr(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                in_stream.as_ptr(),
+                ptr::null(),
+                0,
+                progress_ptr,
+            )
+        };
+
+        if let Some(e) = in_stream.take_error().or(out_stream.take_error()) {
+            return Err(e);
+        }
+        check_progress(progress.as_mut(), res)?;
+        Ok(())
+    }
+
+    /// Returns the raw encoder handle, for use with APIs not covered by this wrapper.
+    pub fn as_ptr(&self) -> CLzma2EncHandle {
+        self.handle
+    }
+}
+
+/// Returns the block size `Lzma2Enc_SetProps` ends up using for `props`.
+fn normalized_block_size(props: &CLzma2EncProps) -> u64 {
+    let mut props = *props;
+    unsafe { Lzma2EncProps_Normalize(&mut props) };
+    props.blockSize
+}
+
+/// Returns an upper bound for the size of the LZMA2 stream for `input_size` bytes split into `block_size` blocks.
+fn max_encoded_size(input_size: usize, block_size: u64) -> usize {
+    // Every block starts a new chunk. Incompressible data is stored in chunks of at most 64 KiB
+    // with a 3 byte header, and chunks can be cut short, so assume one header per 32 KiB.
+    // Compressed chunks are only used when they are smaller than that. Plus the end byte.
+    let block_size = block_size.clamp(1, input_size.max(1) as u64) as usize;
+    let headers = input_size.div_ceil(block_size) * (block_size / (1 << 15) + 1);
+    input_size + headers * 3 + 1
+}
+
+impl Drop for Lzma2Encoder {
+    fn drop(&mut self) {
+        unsafe { Lzma2Enc_Destroy(self.handle) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    fn sample_data(len: u32) -> Vec<u8> {
+        (0..len).map(|x| (x % 251) as u8 ^ (x >> 13) as u8).collect()
+    }
+
+    fn decode(compressed: &[u8], prop: u8, size: usize) -> Vec<u8> {
+        let alloc = Allocator::default();
+        let mut output = vec![0u8; size];
+        let mut output_size = size;
+        let mut input_size = compressed.len();
+        let mut status = ELzmaStatus::LZMA_STATUS_NOT_SPECIFIED;
+        let res = unsafe {
+            Lzma2Decode(
+                output.as_mut_ptr(),
+                &mut output_size,
+                compressed.as_ptr(),
+                &mut input_size,
+                prop,
+                ELzmaFinishMode::LZMA_FINISH_END,
+                &mut status,
+                alloc.as_ref(),
+            )
+        };
+        assert_eq!(res, SZ_OK as i32);
+        assert_eq!(status, ELzmaStatus::LZMA_STATUS_FINISHED_WITH_MARK);
+        output.truncate(output_size);
+        output
+    }
+
+    #[test]
+    fn test_lzma2_round_trip() {
+        let input = sample_data(4 << 20);
+        let mut encoder = Lzma2Encoder::with_options(&Lzma2EncoderOptions::preset(3)).unwrap();
+
+        let max_in = Arc::new(AtomicU64::new(0));
+        let progress_max_in = max_in.clone();
+        encoder.set_progress(move |in_size, _| {
+            if in_size != u64::MAX {
+                progress_max_in.fetch_max(in_size, Ordering::Relaxed);
+            }
+            ControlFlow::Continue(())
+        });
+
+        let compressed = encoder.encode_to_vec(&input).unwrap();
+        assert!(compressed.len() < input.len());
+        assert!(max_in.load(Ordering::Relaxed) > 0);
+        assert_eq!(decode(&compressed, encoder.write_properties(), input.len()), input);
+    }
+
+    #[test]
+    fn test_lzma2_multithreaded_round_trip() {
+        let input = sample_data(3 << 20);
+        let options = Lzma2EncoderOptions::preset(1)
+            .block_size(1 << 18)
+            .block_threads(4);
+        let mut encoder = Lzma2Encoder::with_options(&options).unwrap();
+
+        let compressed = encoder.encode_to_vec(&input).unwrap();
+        assert_eq!(decode(&compressed, encoder.write_properties(), input.len()), input);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_lzma2_stream_round_trip() {
+        let input = sample_data(3 << 20);
+        let options = Lzma2EncoderOptions::preset(1)
+            .block_size(1 << 18)
+            .block_threads(4);
+        let mut encoder = Lzma2Encoder::with_options(&options).unwrap();
+
+        let mut streamed = Vec::new();
+        encoder.encode_stream(&mut std::io::Cursor::new(&input), &mut streamed).unwrap();
+        assert_eq!(decode(&streamed, encoder.write_properties(), input.len()), input);
+    }
+
+    #[test]
+    fn test_lzma2_delta_round_trip() {
+        // A slowly rising 16-bit stereo signal, which compresses far better as differences.
+        let input: Vec<u8> = (0..1 << 18u32)
+            .flat_map(|x| {
+                let left = (x * 3 + (x >> 5) % 7) as u16;
+                let right = (x * 5) as u16;
+                [left.to_le_bytes(), right.to_le_bytes()].concat()
+            })
+            .collect();
+        let mut encoder = Lzma2Encoder::with_options(&Lzma2EncoderOptions::preset(3)).unwrap();
+
+        let plain = encoder.encode_to_vec(&input).unwrap();
+        let mut delta = DeltaFilter::new(4).unwrap();
+        let filtered = encoder.encode_to_vec_with_delta(&input, &mut delta).unwrap();
+        assert!(filtered.len() < plain.len());
+
+        let mut decoded = decode(&filtered, encoder.write_properties(), input.len());
+        DeltaFilter::new(4).unwrap().decode(&mut decoded);
+        assert_eq!(decoded, input);
+    }
+
+    #[test]
+    fn test_lzma2_incompressible_round_trip() {
+        // xorshift noise doesn't compress, so LZMA2 stores it in uncompressed chunks.
+        let mut state = 0x2545F491u32;
+        let input: Vec<u8> = (0..200_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+
+        let mut encoder = Lzma2Encoder::new().unwrap();
+        let compressed = encoder.encode_to_vec(&input).unwrap();
+        assert_eq!(decode(&compressed, encoder.write_properties(), input.len()), input);
+    }
+
+    #[test]
+    fn test_lzma2_small_blocks_incompressible_round_trip() {
+        // Every small block adds chunk headers, which must fit into the output buffer.
+        let mut state = 0x9E37_79B9u32;
+        let input: Vec<u8> = (0..100_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+
+        for block_size in [1 << 10, 5000] {
+            let options = Lzma2EncoderOptions::preset(1).block_size(block_size);
+            let mut encoder = Lzma2Encoder::with_options(&options).unwrap();
+            let compressed = encoder.encode_to_vec(&input).unwrap();
+            assert!(compressed.len() > input.len());
+            assert_eq!(decode(&compressed, encoder.write_properties(), input.len()), input);
+        }
+    }
+
+    #[test]
+    fn test_lzma2_progress_cancel() {
+        let input = sample_data(3 << 20);
+        let single = Lzma2EncoderOptions::preset(1);
+        let multi = Lzma2EncoderOptions::preset(1).block_size(1 << 18).block_threads(4);
+        for options in [single, multi] {
+            let mut encoder = Lzma2Encoder::with_options(&options).unwrap();
+            encoder.set_progress(|_, _| ControlFlow::Break(()));
+            assert_eq!(encoder.encode_to_vec(&input), Err(LzmaError::Cancelled));
+        }
+    }
+
+    #[test]
+    fn test_lzma2_options_validation() {
+        assert_eq!(Lzma2EncoderOptions::default().block_threads(0).build(), Err(LzmaError::Param));
+        let lzma = LzmaEncoderOptions::preset(5).lc(4).lp(1);
+        assert_eq!(Lzma2EncoderOptions::from_lzma(lzma).build(), Err(LzmaError::Param));
+        assert!(Lzma2EncoderOptions::preset(9).block_size(LZMA2_BLOCK_SIZE_SOLID).build().is_ok());
+    }
+}
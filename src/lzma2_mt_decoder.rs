@@ -0,0 +1,267 @@
+use crate::progress::{check_progress, ProgressBridge, ProgressFn};
+use crate::streams::{SeqInStream, SeqOutStream};
+use crate::*;
+use core::ops::ControlFlow;
+use core::ptr;
+use std::io::{self, Read, Write};
+
+/// Builder for `CLzma2DecMtProps`.
+///
+/// Multithreaded decoding splits the stream at chunks which reset the dictionary,
+/// so it only speeds up streams encoded in multiple blocks (e.g. by a multithreaded
+/// [`Lzma2Encoder`]). Other streams are decoded on a single thread.
+///
+/// When the crate is built with the `st` feature, only the single-threaded settings apply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Lzma2MtDecoderOptions {
+    threads: Option<u32>,
+    in_buf_size_st: Option<usize>,
+    out_step_st: Option<usize>,
+    in_buf_size_mt: Option<usize>,
+}
+
+impl Lzma2MtDecoderOptions {
+    /// Sets the number of decoder threads. Ignored with the `st` feature.
+    pub fn threads(mut self, threads: u32) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Sets the size of the input buffer used for single-threaded decoding (`inBufSize_ST`).
+    pub fn in_buf_size_st(mut self, size: usize) -> Self {
+        self.in_buf_size_st = Some(size);
+        self
+    }
+
+    /// Sets how much data is decoded between writes when single-threaded (`outStep_ST`).
+    pub fn out_step_st(mut self, size: usize) -> Self {
+        self.out_step_st = Some(size);
+        self
+    }
+
+    /// Sets the size of the input buffers used by each thread (`inBufSize_MT`). Ignored with the `st` feature.
+    pub fn in_buf_size_mt(mut self, size: usize) -> Self {
+        self.in_buf_size_mt = Some(size);
+        self
+    }
+
+    /// Validates the options and produces decoder properties.
+    ///
+    /// # Returns
+    /// * `Result<CLzma2DecMtProps, LzmaError>` - The properties with unset values taken
+    ///   from `Lzma2DecMtProps_Init`, or [`LzmaError::Param`] if any value is zero.
+    pub fn build(&self) -> Result<CLzma2DecMtProps, LzmaError> {
+        let valid = self.threads != Some(0)
+            && self.in_buf_size_st != Some(0)
+            && self.out_step_st != Some(0)
+            && self.in_buf_size_mt != Some(0);
+        if !valid {
+            return Err(LzmaError::Param);
+        }
+
+        let mut props = CLzma2DecMtProps::default();
+        unsafe { Lzma2DecMtProps_Init(&mut props) };
+        if let Some(size) = self.in_buf_size_st {
+            props.inBufSize_ST = size;
+        }
+        if let Some(size) = self.out_step_st {
+            props.outStep_ST = size;
+        }
+
+        #[cfg(not(feature = "st"))]
+        {
+            if let Some(threads) = self.threads {
+                props.numThreads = threads;
+            }
+            if let Some(size) = self.in_buf_size_mt {
+                props.inBufSize_MT = size;
+            }
+        }
+
+        Ok(props)
+    }
+}
+
+/// Result of a successful [`Lzma2MtDecoder::decode_stream`] call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lzma2MtDecodeStats {
+    /// Number of compressed bytes processed.
+    pub in_processed: u64,
+    /// Whether the stream was actually decoded on multiple threads.
+    pub multithreaded: bool,
+}
+
+/// Safe owning handle around the multithreaded LZMA2 decoder (`CLzma2DecMtHandle`).
+///
+/// With the `st` feature, or a single thread, this behaves like a streaming [`Lzma2Decoder`].
+pub struct Lzma2MtDecoder {
+    handle: CLzma2DecMtHandle,
+    props: CLzma2DecMtProps,
+    // Never read, but boxed and kept alive since the decoder keeps a pointer to it.
+    _alloc: Box<Allocator>,
+    progress: Option<Box<ProgressFn>>,
+}
+
+// The handle is exclusively owned and only ever accessed through `&mut self`.
+unsafe impl Send for Lzma2MtDecoder {}
+
+impl Lzma2MtDecoder {
+    /// Creates a new decoder with default options (a single thread).
+    ///
+    /// # Returns
+    /// * `Result<Self, LzmaError>` - The decoder, or [`LzmaError::Mem`] if allocation failed.
+    pub fn new() -> Result<Self, LzmaError> {
+        Self::with_options(&Lzma2MtDecoderOptions::default())
+    }
+
+    /// Creates a new decoder from validated [`Lzma2MtDecoderOptions`].
+    pub fn with_options(options: &Lzma2MtDecoderOptions) -> Result<Self, LzmaError> {
+        let props = options.build()?;
+        let alloc = Box::new(Allocator::default());
+        let handle = unsafe { Lzma2DecMt_Create(alloc.as_ref().as_ref(), alloc.as_ref().as_ref()) };
+        if handle.is_null() {
+            return Err(LzmaError::Mem);
+        }
+
+        Ok(Self {
+            handle,
+            props,
+            _alloc: alloc,
+            progress: None,
+        })
+    }
+
+    /// Sets a callback receiving `(in_size, out_size)` as decompression progresses.
+    /// Returning [`ControlFlow::Break`] cancels the operation with [`LzmaError::Cancelled`].
+    pub fn set_progress<F: FnMut(u64, u64) -> ControlFlow<()> + Send + 'static>(&mut self, progress: F) {
+        self.progress = Some(Box::new(progress));
+    }
+
+    /// Decompresses a raw LZMA2 stream from `input` into `output`.
+    ///
+    /// The streams may be accessed from worker threads, hence the `Send` bounds.
+    /// Note that `input` may be read past the end of the LZMA2 stream.
+    ///
+    /// # Arguments
+    /// * `prop` - The LZMA2 properties (dictionary size) byte.
+    /// * `input` - Reader positioned at the start of the raw LZMA2 stream.
+    /// * `output` - Writer receiving the decompressed data.
+    /// * `unpacked_size` - Decompressed size if known; decoding stops once it is reached.
+    pub fn decode_stream<R: Read + Send, W: Write + Send>(
+        &mut self,
+        prop: u8,
+        input: &mut R,
+        output: &mut W,
+        unpacked_size: Option<u64>,
+    ) -> io::Result<Lzma2MtDecodeStats> {
+        let mut in_stream = SeqInStream::new(input);
+        let mut out_stream = SeqOutStream::new(output);
+        let mut progress = self.progress.as_deref_mut().map(ProgressBridge::new);
+        let progress_ptr = progress.as_mut().map_or(ptr::null(), |p| p.as_ptr());
+
+        let mut in_processed = 0;
+        let mut is_mt = 0;
+        let res = unsafe {
+            Lzma2DecMt_Decode(
+                self.handle,
+                prop,
+                &self.props,
+                out_stream.as_ptr(),
+                unpacked_size.as_ref().map_or(ptr::null(), |size| size),
+                // Require the stream to be complete, rather than allowing partial output.
+                1,
+                in_stream.as_ptr(),
+                &mut in_processed,
+                &mut is_mt,
+                progress_ptr,
+            )
+        };
+
+        if let Some(e) = in_stream.take_error().or(out_stream.take_error()) {
+            return Err(e);
+        }
+        check_progress(progress.as_mut(), res)?;
+        Ok(Lzma2MtDecodeStats {
+            in_processed,
+            multithreaded: is_mt != 0,
+        })
+    }
+
+    /// Returns the raw decoder handle, for use with APIs not covered by this wrapper.
+    pub fn as_ptr(&self) -> CLzma2DecMtHandle {
+        self.handle
+    }
+}
+
+impl Drop for Lzma2MtDecoder {
+    fn drop(&mut self) {
+        unsafe { Lzma2DecMt_Destroy(self.handle) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn compress_blocks(input: &[u8]) -> (u8, Vec<u8>) {
+        let options = Lzma2EncoderOptions::preset(1)
+            .block_size(1 << 18)
+            .block_threads(4);
+        let mut encoder = Lzma2Encoder::with_options(&options).unwrap();
+        (encoder.write_properties(), encoder.encode_to_vec(input).unwrap())
+    }
+
+    #[test]
+    fn test_lzma2_mt_decoder_round_trip() {
+        let input: Vec<u8> = (0..4_000_000u32).map(|x| (x % 251) as u8 ^ (x >> 14) as u8).collect();
+        let (prop, compressed) = compress_blocks(&input);
+
+        for threads in [1, 4] {
+            let options = Lzma2MtDecoderOptions::default()
+                .threads(threads)
+                .in_buf_size_st(1 << 16)
+                .out_step_st(1 << 16)
+                .in_buf_size_mt(1 << 16);
+            let mut decoder = Lzma2MtDecoder::with_options(&options).unwrap();
+
+            let mut output = Vec::new();
+            decoder
+                .decode_stream(prop, &mut Cursor::new(&compressed), &mut output, Some(input.len() as u64))
+                .unwrap();
+            assert_eq!(output, input);
+        }
+    }
+
+    #[test]
+    fn test_lzma2_mt_decoder_single_thread_stats() {
+        let input: Vec<u8> = (0..100_000u32).map(|x| (x % 7) as u8).collect();
+        let (prop, compressed) = compress_blocks(&input);
+
+        let mut decoder = Lzma2MtDecoder::new().unwrap();
+        let mut output = Vec::new();
+        let stats = decoder
+            .decode_stream(prop, &mut Cursor::new(&compressed), &mut output, None)
+            .unwrap();
+        assert_eq!(output, input);
+        assert_eq!(stats.in_processed, compressed.len() as u64);
+        assert!(!stats.multithreaded);
+    }
+
+    #[test]
+    fn test_lzma2_mt_decoder_truncated_stream() {
+        let input: Vec<u8> = (0..100_000u32).map(|x| (x % 7) as u8).collect();
+        let (prop, compressed) = compress_blocks(&input);
+
+        let options = Lzma2MtDecoderOptions::default().threads(2);
+        let mut decoder = Lzma2MtDecoder::with_options(&options).unwrap();
+        let truncated = &compressed[..compressed.len() / 2];
+        let err = decoder
+            .decode_stream(prop, &mut Cursor::new(truncated), &mut Vec::new(), None)
+            .unwrap_err();
+        // Depending on where the stream was cut, it is reported as truncated or corrupt.
+        assert!(matches!(err.kind(), io::ErrorKind::UnexpectedEof | io::ErrorKind::InvalidData));
+
+        assert_eq!(Lzma2MtDecoderOptions::default().threads(0).build(), Err(LzmaError::Param));
+    }
+}
@@ -0,0 +1,94 @@
+use crate::buffered::{BufferedDecoder, Step, StepDecoder};
+use crate::*;
+use std::io::{self, BufRead, Read};
+
+/// Streaming LZMA2 decompressor implementing [`Read`] and [`BufRead`].
+///
+/// Decodes a raw LZMA2 stream (as stored in `.xz` blocks and 7z archives),
+/// whose properties byte is stored elsewhere.
+pub struct Lzma2Reader<R> {
+    inner: BufferedDecoder<R, Lzma2Decoder>,
+}
+
+impl<R: Read> Lzma2Reader<R> {
+    /// Creates a reader for a raw LZMA2 stream.
+    ///
+    /// # Arguments
+    /// * `inner` - Reader positioned at the start of the raw LZMA2 stream.
+    /// * `prop` - The LZMA2 properties (dictionary size) byte.
+    pub fn new(inner: R, prop: u8) -> io::Result<Self> {
+        Ok(Self {
+            inner: BufferedDecoder::new(inner, Lzma2Decoder::new(prop)?),
+        })
+    }
+
+    /// Returns a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        self.inner.get_ref()
+    }
+
+    /// Consumes the reader, returning the inner reader.
+    ///
+    /// Note that the inner reader may have been read past the end of the LZMA2 stream.
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+}
+
+impl<R: Read> BufRead for Lzma2Reader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt)
+    }
+}
+
+impl<R: Read> Read for Lzma2Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl StepDecoder for Lzma2Decoder {
+    const FORMAT: &'static str = "LZMA2";
+
+    fn step(&mut self, input: &[u8], output: &mut [u8], _input_eof: bool) -> io::Result<Step> {
+        let progress = self.decode(input, output, ELzmaFinishMode::LZMA_FINISH_ANY)?;
+        Ok(Step {
+            consumed: progress.consumed,
+            produced: progress.produced,
+            finished: progress.is_finished(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_lzma2_reader_round_trip() {
+        let input: Vec<u8> = (0..500_000u32).map(|x| (x % 251) as u8 ^ (x / 1024) as u8).collect();
+        let mut encoder = Lzma2Encoder::new().unwrap();
+        let compressed = encoder.encode_to_vec(&input).unwrap();
+
+        let mut output = Vec::new();
+        let mut reader = Lzma2Reader::new(Cursor::new(&compressed), encoder.write_properties()).unwrap();
+        reader.read_to_end(&mut output).unwrap();
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn test_lzma2_reader_truncated_stream() {
+        let input: Vec<u8> = (0..100_000u32).map(|x| (x % 13) as u8).collect();
+        let mut encoder = Lzma2Encoder::new().unwrap();
+        let compressed = encoder.encode_to_vec(&input).unwrap();
+
+        let reader = Lzma2Reader::new(Cursor::new(&compressed[..compressed.len() - 1]), encoder.write_properties());
+        let err = reader.unwrap().read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,68 @@
+use core::hint;
+use core::mem;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+const UNINIT: u8 = 0;
+const INITIALIZING: u8 = 1;
+const READY: u8 = 2;
+
+/// Runs a global initializer (e.g. SDK table generation) exactly once.
+///
+/// `core` has no `Once`, and the crate needs to work without `std`.
+pub(crate) struct InitOnce {
+    state: AtomicU8,
+}
+
+impl InitOnce {
+    pub(crate) const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(UNINIT),
+        }
+    }
+
+    /// Runs `init` if this is the first call, otherwise waits until the first call has finished.
+    ///
+    /// If `init` panics, the next call runs it again.
+    pub(crate) fn call(&self, init: impl FnOnce()) {
+        loop {
+            match self.state.compare_exchange_weak(UNINIT, INITIALIZING, Ordering::Acquire, Ordering::Acquire) {
+                Ok(_) => break,
+                Err(READY) => return,
+                // Another thread is running the initializer, wait until it is done.
+                Err(_) => hint::spin_loop(),
+            }
+        }
+
+        let guard = ResetOnUnwind(&self.state);
+        init();
+        mem::forget(guard);
+        self.state.store(READY, Ordering::Release);
+    }
+}
+
+/// Resets the state when dropped, which only happens if the initializer panics.
+struct ResetOnUnwind<'a>(&'a AtomicU8);
+
+impl Drop for ResetOnUnwind<'_> {
+    fn drop(&mut self) {
+        self.0.store(UNINIT, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic;
+
+    #[test]
+    fn test_init_once_retries_after_panic() {
+        let once = InitOnce::new();
+        let res = panic::catch_unwind(|| once.call(|| panic!("init failed")));
+        assert!(res.is_err());
+
+        let mut calls = 0;
+        once.call(|| calls += 1);
+        once.call(|| calls += 1);
+        assert_eq!(calls, 1);
+    }
+}
@@ -0,0 +1,284 @@
+use crate::*;
+
+/// Smallest dictionary size accepted by [`LzmaEncoderOptions`] (4 KiB).
+pub const LZMA_DICT_SIZE_MIN: u32 = 1 << 12;
+
+/// Largest dictionary size accepted by [`LzmaEncoderOptions`] (1536 MiB).
+pub const LZMA_DICT_SIZE_MAX: u32 = 1536 << 20;
+
+/// Match finder used by the LZMA encoder.
+///
+/// Binary tree (`Bt*`) match finders give better compression, hash chain (`Hc*`)
+/// match finders are faster. The number is how many bytes are hashed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MatchFinder {
+    Bt2,
+    Bt3,
+    Bt4,
+    Hc4,
+    Hc5,
+}
+
+impl MatchFinder {
+    /// Returns the `(btMode, numHashBytes)` pair used by `CLzmaEncProps`.
+    fn to_raw(self) -> (i32, i32) {
+        match self {
+            MatchFinder::Bt2 => (1, 2),
+            MatchFinder::Bt3 => (1, 3),
+            MatchFinder::Bt4 => (1, 4),
+            MatchFinder::Hc4 => (0, 4),
+            MatchFinder::Hc5 => (0, 5),
+        }
+    }
+}
+
+/// Compression mode (`algo` in `CLzmaEncProps`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LzmaMode {
+    /// Fast mode, used by presets 0-4.
+    Fast,
+    /// Normal (optimal parsing) mode, used by presets 5-9.
+    Normal,
+}
+
+/// Builder for validated `CLzmaEncProps`.
+///
+/// Unset values are derived from the preset level by `LzmaEncProps_Normalize`,
+/// and the final combination is validated before it can reach the C encoder.
+///
+/// ```ignore
+/// let props = LzmaEncoderOptions::preset(9)
+///     .extreme()
+///     .dict_size(1 << 24)
+///     .build()?;
+/// let encoder = LzmaEncoder::with_props(&props)?;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LzmaEncoderOptions {
+    level: u32,
+    dict_size: Option<u32>,
+    lc: Option<u32>,
+    lp: Option<u32>,
+    pb: Option<u32>,
+    mode: Option<LzmaMode>,
+    fast_bytes: Option<u32>,
+    match_finder: Option<MatchFinder>,
+    match_cycles: Option<u32>,
+    threads: Option<u32>,
+    end_marker: bool,
+    reduce_size: Option<u64>,
+}
+
+impl Default for LzmaEncoderOptions {
+    /// Same settings as `LzmaEncProps_Init` (preset 5).
+    fn default() -> Self {
+        Self::preset(5)
+    }
+}
+
+impl LzmaEncoderOptions {
+    /// Creates options for the given preset level (0 = fastest, 9 = best compression).
+    ///
+    /// Levels above 9 are rejected by [`LzmaEncoderOptions::build`].
+    pub fn preset(level: u32) -> Self {
+        Self {
+            level,
+            dict_size: None,
+            lc: None,
+            lp: None,
+            pb: None,
+            mode: None,
+            fast_bytes: None,
+            match_finder: None,
+            match_cycles: None,
+            threads: None,
+            end_marker: false,
+            reduce_size: None,
+        }
+    }
+
+    /// Slower but stronger variant of the current preset: normal mode,
+    /// binary tree match finder and maximum number of fast bytes.
+    pub fn extreme(mut self) -> Self {
+        self.mode = Some(LzmaMode::Normal);
+        self.match_finder = Some(MatchFinder::Bt4);
+        self.fast_bytes = Some(273);
+        self
+    }
+
+    /// Sets the dictionary size in bytes.
+    pub fn dict_size(mut self, dict_size: u32) -> Self {
+        self.dict_size = Some(dict_size);
+        self
+    }
+
+    /// Sets the number of literal context bits (0-8, `lc + lp <= 4`).
+    pub fn lc(mut self, lc: u32) -> Self {
+        self.lc = Some(lc);
+        self
+    }
+
+    /// Sets the number of literal position bits (0-4, `lc + lp <= 4`).
+    pub fn lp(mut self, lp: u32) -> Self {
+        self.lp = Some(lp);
+        self
+    }
+
+    /// Sets the number of position bits (0-4).
+    pub fn pb(mut self, pb: u32) -> Self {
+        self.pb = Some(pb);
+        self
+    }
+
+    /// Sets the compression mode.
+    pub fn mode(mut self, mode: LzmaMode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Sets the number of fast bytes, a.k.a. nice length (5-273).
+    pub fn fast_bytes(mut self, fast_bytes: u32) -> Self {
+        self.fast_bytes = Some(fast_bytes);
+        self
+    }
+
+    /// Sets the match finder.
+    pub fn match_finder(mut self, match_finder: MatchFinder) -> Self {
+        self.match_finder = Some(match_finder);
+        self
+    }
+
+    /// Sets the number of match finder cycles, a.k.a. search depth (1 to 2^30).
+    pub fn match_cycles(mut self, match_cycles: u32) -> Self {
+        self.match_cycles = Some(match_cycles);
+        self
+    }
+
+    /// Sets the number of encoder threads (1 or 2).
+    pub fn threads(mut self, threads: u32) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Sets whether the stream is terminated with an end marker.
+    pub fn end_marker(mut self, end_marker: bool) -> Self {
+        self.end_marker = end_marker;
+        self
+    }
+
+    /// Sets the expected size of the input, which lets the encoder reduce the dictionary size.
+    pub fn reduce_size(mut self, reduce_size: u64) -> Self {
+        self.reduce_size = Some(reduce_size);
+        self
+    }
+
+    /// Validates the options and produces normalized encoder properties.
+    ///
+    /// # Returns
+    /// * `Result<CLzmaEncProps, LzmaError>` - The properties with all defaults filled in
+    ///   by `LzmaEncProps_Normalize`, or [`LzmaError::Param`] if any value is out of range.
+    pub fn build(&self) -> Result<CLzmaEncProps, LzmaError> {
+        let in_range = |value: Option<u32>, min: u32, max: u32| value.is_none_or(|v| (min..=max).contains(&v));
+        let valid = self.level <= 9
+            && in_range(self.dict_size, LZMA_DICT_SIZE_MIN, LZMA_DICT_SIZE_MAX)
+            && in_range(self.lc, 0, 8)
+            && in_range(self.lp, 0, 4)
+            && in_range(self.pb, 0, 4)
+            && in_range(self.fast_bytes, 5, 273)
+            && in_range(self.match_cycles, 1, 1 << 30)
+            && in_range(self.threads, 1, 2);
+        if !valid {
+            return Err(LzmaError::Param);
+        }
+
+        let mut props = CLzmaEncProps::default();
+        unsafe { LzmaEncProps_Init(&mut props) };
+        props.level = self.level as i32;
+        props.writeEndMark = self.end_marker as u32;
+        if let Some(dict_size) = self.dict_size {
+            props.dictSize = dict_size;
+        }
+        if let Some(lc) = self.lc {
+            props.lc = lc as i32;
+        }
+        if let Some(lp) = self.lp {
+            props.lp = lp as i32;
+        }
+        if let Some(pb) = self.pb {
+            props.pb = pb as i32;
+        }
+        if let Some(mode) = self.mode {
+            props.algo = (mode == LzmaMode::Normal) as i32;
+        }
+        if let Some(fast_bytes) = self.fast_bytes {
+            props.fb = fast_bytes as i32;
+        }
+        if let Some(match_finder) = self.match_finder {
+            (props.btMode, props.numHashBytes) = match_finder.to_raw();
+        }
+        if let Some(match_cycles) = self.match_cycles {
+            props.mc = match_cycles;
+        }
+        if let Some(threads) = self.threads {
+            props.numThreads = threads as i32;
+        }
+        if let Some(reduce_size) = self.reduce_size {
+            props.reduceSize = reduce_size;
+        }
+
+        // Fill in the values derived from the level, then check the combination as a whole.
+        unsafe { LzmaEncProps_Normalize(&mut props) };
+        if props.lc + props.lp > 4 {
+            return Err(LzmaError::Param);
+        }
+
+        Ok(props)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_presets() {
+        for level in 0..=9 {
+            let props = LzmaEncoderOptions::preset(level).build().unwrap();
+            assert_eq!(props.level, level as i32);
+            assert!(props.dictSize >= LZMA_DICT_SIZE_MIN);
+            assert!(props.lc + props.lp <= 4);
+        }
+
+        let props = LzmaEncoderOptions::preset(1).extreme().match_finder(MatchFinder::Hc5).build().unwrap();
+        assert_eq!((props.algo, props.fb, props.btMode, props.numHashBytes), (1, 273, 0, 5));
+    }
+
+    #[test]
+    fn test_options_rejects_invalid() {
+        assert_eq!(LzmaEncoderOptions::preset(10).build(), Err(LzmaError::Param));
+        assert_eq!(LzmaEncoderOptions::default().lc(3).lp(2).build(), Err(LzmaError::Param));
+        assert_eq!(LzmaEncoderOptions::default().lc(5).build(), Err(LzmaError::Param));
+        assert_eq!(LzmaEncoderOptions::default().fast_bytes(274).build(), Err(LzmaError::Param));
+        assert_eq!(LzmaEncoderOptions::default().dict_size(1024).build(), Err(LzmaError::Param));
+        assert_eq!(LzmaEncoderOptions::default().threads(3).build(), Err(LzmaError::Param));
+    }
+
+    #[test]
+    fn test_options_round_trip() {
+        let input = b"Builder configured LZMA. Builder configured LZMA. Builder configured LZMA.";
+        let options = LzmaEncoderOptions::preset(9).lc(0).lp(2).pb(2).end_marker(true);
+
+        let mut encoder = LzmaEncoder::with_options(&options).unwrap();
+        let props = encoder.write_properties().unwrap();
+        let mut compressed = [0u8; 256];
+        let compressed_size = encoder.encode_slice(input, &mut compressed, true).unwrap();
+
+        let mut decoder = LzmaDecoder::new(&props).unwrap();
+        let mut output = [0u8; 256];
+        let progress = decoder
+            .decode(&compressed[..compressed_size], &mut output, ELzmaFinishMode::LZMA_FINISH_ANY)
+            .unwrap();
+        assert!(progress.is_finished());
+        assert_eq!(&output[..progress.produced], input);
+    }
+}
@@ -0,0 +1,263 @@
+use crate::*;
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Size of the PPMd properties stored in `.7z` archives: the order, then the memory size (LE).
+pub const PPMD7_PROPS_SIZE: usize = 5;
+
+/// A PPMd variant H model (`CPpmd7`) with its allocated memory.
+struct Ppmd7Model {
+    ppmd: Box<CPpmd7>,
+    alloc: Allocator,
+    order: u32,
+    memory_size: u32,
+}
+
+impl Ppmd7Model {
+    fn new(order: u32, memory_size: u32) -> Result<Self, LzmaError> {
+        if !(PPMD7_MIN_ORDER..=PPMD7_MAX_ORDER).contains(&order)
+            || !(PPMD7_MIN_MEM_SIZE..=PPMD7_MAX_MEM_SIZE).contains(&memory_size)
+        {
+            return Err(LzmaError::Param);
+        }
+
+        let mut ppmd = Box::<CPpmd7>::default();
+        let alloc = Allocator::default();
+        unsafe { Ppmd7_Construct(ppmd.as_mut()) };
+        if unsafe { Ppmd7_Alloc(ppmd.as_mut(), memory_size, alloc.as_ref()) } == 0 {
+            return Err(LzmaError::Mem);
+        }
+
+        Ok(Self {
+            ppmd,
+            alloc,
+            order,
+            memory_size,
+        })
+    }
+
+    fn properties(&self) -> [u8; PPMD7_PROPS_SIZE] {
+        let mut props = [0u8; PPMD7_PROPS_SIZE];
+        props[0] = self.order as u8;
+        props[1..].copy_from_slice(&self.memory_size.to_le_bytes());
+        props
+    }
+}
+
+impl Drop for Ppmd7Model {
+    fn drop(&mut self) {
+        unsafe { Ppmd7_Free(self.ppmd.as_mut(), self.alloc.as_ref()) };
+    }
+}
+
+// The model is exclusively owned and only ever accessed through `&mut self`.
+unsafe impl Send for Ppmd7Model {}
+
+/// `IByteOut` appending to a [`Vec`].
+#[repr(C)]
+struct ByteOutVec {
+    vt: IByteOut,
+    output: Vec<u8>,
+}
+
+unsafe extern "C" fn byte_out_vec_write(p: IByteOutPtr, b: Byte) {
+    let stream = &mut *(p as *mut ByteOutVec);
+    stream.output.push(b);
+}
+
+/// `IByteIn` over a slice. Reads past the end return 0 and set `extra`, like `CByteInToLook` in `7zDec.c`.
+#[repr(C)]
+struct ByteInSlice<'a> {
+    vt: IByteIn,
+    input: &'a [u8],
+    pos: usize,
+    extra: bool,
+}
+
+unsafe extern "C" fn byte_in_slice_read(p: IByteInPtr) -> Byte {
+    let stream = &mut *(p as *mut ByteInSlice);
+    match stream.input.get(stream.pos) {
+        Some(&b) => {
+            stream.pos += 1;
+            b
+        }
+        None => {
+            stream.extra = true;
+            0
+        }
+    }
+}
+
+/// PPMd variant H encoder (`Ppmd7z_EncodeSymbols`) with the 7z range coder, as used by `.7z` archives.
+///
+/// Usually compresses text much better than LZMA, at the cost of symmetric (slow) decompression.
+pub struct Ppmd7Encoder {
+    model: Ppmd7Model,
+}
+
+impl Ppmd7Encoder {
+    /// Creates an encoder.
+    ///
+    /// # Arguments
+    /// * `order` - Model order, `PPMD7_MIN_ORDER..=PPMD7_MAX_ORDER` (7-Zip uses 6 by default).
+    /// * `memory_size` - Model memory in bytes, `PPMD7_MIN_MEM_SIZE..=PPMD7_MAX_MEM_SIZE`.
+    ///   The decoder needs the same amount.
+    ///
+    /// # Returns
+    /// * `Result<Self, LzmaError>` - The encoder, [`LzmaError::Param`] for out of range values,
+    ///   or [`LzmaError::Mem`] if the model memory couldn't be allocated.
+    pub fn new(order: u32, memory_size: u32) -> Result<Self, LzmaError> {
+        Ok(Self {
+            model: Ppmd7Model::new(order, memory_size)?,
+        })
+    }
+
+    /// Returns the `.7z` coder properties, required by [`Ppmd7Decoder::from_properties`].
+    pub fn properties(&self) -> [u8; PPMD7_PROPS_SIZE] {
+        self.model.properties()
+    }
+
+    /// Compresses `input` as one stream into a newly allocated buffer.
+    ///
+    /// No end marker is written, the decoder needs to know the size of `input`.
+    pub fn encode_to_vec(&mut self, input: &[u8]) -> Vec<u8> {
+        let mut out_stream = ByteOutVec {
+            vt: IByteOut {
+                Write: Some(byte_out_vec_write),
+            },
+            output: Vec::with_capacity(input.len() / 2 + 16),
+        };
+
+        let ppmd = self.model.ppmd.as_mut();
+        let range = input.as_ptr_range();
+        unsafe {
+            ppmd.rc.enc.Stream = &mut out_stream as *mut ByteOutVec as IByteOutPtr;
+            Ppmd7z_Init_RangeEnc(ppmd);
+            Ppmd7_Init(ppmd, self.model.order);
+            Ppmd7z_EncodeSymbols(ppmd, range.start, range.end);
+            Ppmd7z_Flush_RangeEnc(ppmd);
+        }
+        out_stream.output
+    }
+}
+
+/// PPMd variant H decoder (`Ppmd7z_DecodeSymbol`) for streams written by [`Ppmd7Encoder`] or 7-Zip.
+pub struct Ppmd7Decoder {
+    model: Ppmd7Model,
+}
+
+impl Ppmd7Decoder {
+    /// Creates a decoder with the order and memory size used by the encoder.
+    /// See [`Ppmd7Encoder::new`] for the valid ranges.
+    pub fn new(order: u32, memory_size: u32) -> Result<Self, LzmaError> {
+        Ok(Self {
+            model: Ppmd7Model::new(order, memory_size)?,
+        })
+    }
+
+    /// Creates a decoder from `.7z` coder properties.
+    ///
+    /// # Returns
+    /// * `Result<Self, LzmaError>` - The decoder, or [`LzmaError::Unsupported`] if the
+    ///   properties are malformed, like `7zDec.c`.
+    pub fn from_properties(props: &[u8]) -> Result<Self, LzmaError> {
+        let props: &[u8; PPMD7_PROPS_SIZE] = props.try_into().map_err(|_| LzmaError::Unsupported)?;
+        let memory_size = u32::from_le_bytes([props[1], props[2], props[3], props[4]]);
+        Self::new(props[0] as u32, memory_size).map_err(|e| match e {
+            LzmaError::Param => LzmaError::Unsupported,
+            e => e,
+        })
+    }
+
+    /// Decompresses one stream of `unpacked_size` bytes.
+    ///
+    /// # Returns
+    /// * `Result<Vec<u8>, LzmaError>` - The data, [`LzmaError::InputEof`] if `input` is
+    ///   truncated, or [`LzmaError::Data`] if it is corrupt.
+    pub fn decode_to_vec(&mut self, input: &[u8], unpacked_size: usize) -> Result<Vec<u8>, LzmaError> {
+        let mut in_stream = ByteInSlice {
+            vt: IByteIn {
+                Read: Some(byte_in_slice_read),
+            },
+            input,
+            pos: 0,
+            extra: false,
+        };
+        let mut output = vec![0u8; unpacked_size];
+
+        let ppmd = self.model.ppmd.as_mut();
+        unsafe {
+            Ppmd7_Init(ppmd, self.model.order);
+            ppmd.rc.dec.Stream = &mut in_stream as *mut ByteInSlice as IByteInPtr;
+            if Ppmd7z_RangeDec_Init(&mut ppmd.rc.dec) == 0 {
+                return Err(LzmaError::Data);
+            }
+        }
+
+        for byte in output.iter_mut() {
+            let symbol = unsafe { Ppmd7z_DecodeSymbol(ppmd) };
+            if in_stream.extra {
+                return Err(LzmaError::InputEof);
+            }
+            if symbol < 0 {
+                return Err(LzmaError::Data);
+            }
+            *byte = symbol as u8;
+        }
+
+        // `Ppmd7z_RangeDec_IsFinishedOK`
+        if in_stream.extra || unsafe { ppmd.rc.dec.Code } != 0 {
+            return Err(LzmaError::Data);
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text() -> Vec<u8> {
+        let mut text = Vec::new();
+        for i in 0..2000 {
+            text.extend_from_slice(format!("{{\"id\": {i}, \"name\": \"item {}\", \"enabled\": {}}},\n", i * 7 % 113, i % 3 == 0).as_bytes());
+        }
+        text
+    }
+
+    #[test]
+    fn test_ppmd7_round_trip() {
+        let input = text();
+        for (order, memory_size) in [(6, 16 << 20), (PPMD7_MIN_ORDER, PPMD7_MIN_MEM_SIZE), (32, 1 << 20)] {
+            let mut encoder = Ppmd7Encoder::new(order, memory_size).unwrap();
+            let compressed = encoder.encode_to_vec(&input);
+            assert!(compressed.len() < input.len() / 2, "order {order}");
+
+            let mut decoder = Ppmd7Decoder::from_properties(&encoder.properties()).unwrap();
+            assert_eq!(decoder.decode_to_vec(&compressed, input.len()).unwrap(), input);
+
+            // Both sides reset their model for each stream.
+            assert_eq!(encoder.encode_to_vec(&input), compressed);
+            assert_eq!(decoder.decode_to_vec(&compressed, input.len()).unwrap(), input);
+        }
+    }
+
+    #[test]
+    fn test_ppmd7_errors() {
+        let input = text();
+        let mut encoder = Ppmd7Encoder::new(6, 1 << 20).unwrap();
+        let compressed = encoder.encode_to_vec(&input);
+        let mut decoder = Ppmd7Decoder::new(6, 1 << 20).unwrap();
+
+        let truncated = decoder.decode_to_vec(&compressed[..compressed.len() / 2], input.len());
+        assert_eq!(truncated, Err(LzmaError::InputEof));
+
+        // Asking for fewer bytes leaves the range decoder unfinished.
+        assert_eq!(decoder.decode_to_vec(&compressed, input.len() - 100), Err(LzmaError::Data));
+
+        assert_eq!(Ppmd7Encoder::new(1, 1 << 20).err(), Some(LzmaError::Param));
+        assert_eq!(Ppmd7Decoder::from_properties(&[6, 0, 0, 0]).err(), Some(LzmaError::Unsupported));
+        assert_eq!(Ppmd7Decoder::from_properties(&[6, 0, 0, 0, 0]).err(), Some(LzmaError::Unsupported));
+    }
+}
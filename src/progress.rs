@@ -0,0 +1,86 @@
+#[cfg(feature = "std")]
+use crate::streams::{catch_panic, Panic};
+use crate::*;
+use core::ops::ControlFlow;
+
+/// Callback receiving `(in_size, out_size)` byte counts during compression.
+///
+/// Either value may be `u64::MAX` if it is not known at the time of the call.
+/// Returning [`ControlFlow::Break`] cancels the operation, which then fails with [`LzmaError::Cancelled`].
+/// Multithreaded coders may invoke the callback from worker threads, but never concurrently.
+/// With the `std` feature, a panic in the callback cancels the operation and is resumed once it returned.
+pub type ProgressFn = dyn FnMut(u64, u64) -> ControlFlow<()> + Send;
+
+/// Adapts a Rust closure into an LZMA-SDK `ICompressProgress`.
+///
+/// The vtable must be the first field, so a pointer to it can be cast back to the container.
+#[repr(C)]
+pub(crate) struct ProgressBridge<'a> {
+    vt: ICompressProgress,
+    callback: &'a mut ProgressFn,
+    cancelled: bool,
+    #[cfg(feature = "std")]
+    panic: Option<Panic>,
+}
+
+impl<'a> ProgressBridge<'a> {
+    pub(crate) fn new(callback: &'a mut ProgressFn) -> Self {
+        Self {
+            vt: ICompressProgress {
+                Progress: Some(progress_callback),
+            },
+            callback,
+            cancelled: false,
+            #[cfg(feature = "std")]
+            panic: None,
+        }
+    }
+
+    /// Returns the pointer to pass to the SDK.
+    pub(crate) fn as_ptr(&mut self) -> ICompressProgressPtr {
+        self as *mut Self as ICompressProgressPtr
+    }
+
+    /// Calls the callback, catching a panic so it doesn't unwind into the SDK.
+    #[cfg(feature = "std")]
+    fn call(&mut self, in_size: u64, out_size: u64) -> Option<ControlFlow<()>> {
+        catch_panic(&mut self.panic, || (self.callback)(in_size, out_size))
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn call(&mut self, in_size: u64, out_size: u64) -> Option<ControlFlow<()>> {
+        Some((self.callback)(in_size, out_size))
+    }
+
+    /// Returns true if the callback cancelled the operation, resuming its panic if it panicked.
+    fn cancelled(&mut self) -> bool {
+        #[cfg(feature = "std")]
+        if let Some(payload) = self.panic.take() {
+            std::panic::resume_unwind(payload);
+        }
+        self.cancelled
+    }
+}
+
+/// Like [`check`], but reports `SZ_ERROR_PROGRESS` caused by the callback of `progress` as [`LzmaError::Cancelled`].
+///
+/// Resumes unwinding if the callback panicked.
+pub(crate) fn check_progress(progress: Option<&mut ProgressBridge>, res: SRes) -> Result<(), LzmaError> {
+    let cancelled = progress.is_some_and(|p| p.cancelled());
+    match check(res) {
+        Err(LzmaError::Progress) if cancelled => Err(LzmaError::Cancelled),
+        res => res,
+    }
+}
+
+unsafe extern "C" fn progress_callback(p: ICompressProgressPtr, in_size: UInt64, out_size: UInt64) -> SRes {
+    let bridge = &mut *(p as *mut ProgressBridge);
+    match bridge.call(in_size, out_size) {
+        Some(ControlFlow::Continue(())) => SZ_OK as SRes,
+        Some(ControlFlow::Break(())) => {
+            bridge.cancelled = true;
+            SZ_ERROR_PROGRESS as SRes
+        }
+        None => SZ_ERROR_PROGRESS as SRes,
+    }
+}
@@ -0,0 +1,192 @@
+use crate::*;
+use core::fmt;
+
+/// Smallest dictionary size used by the decoder (`LZMA_DIC_MIN` in `LzmaDec.c`).
+const LZMA_DIC_MIN: u32 = 1 << 12;
+
+/// Number of probabilities not dependent on `lc`/`lp` (`NUM_BASE_PROBS` in `LzmaDec.c`).
+const NUM_BASE_PROBS: u64 = 1984;
+
+/// Number of literal probabilities per literal state (`LZMA_LIT_SIZE` in `LzmaDec.c`).
+const LZMA_LIT_SIZE: u64 = 0x300;
+
+/// Decoded form of the 5-byte LZMA properties header.
+///
+/// ```ignore
+/// Offset  Size  Description
+/// 0       1     (pb * 5 + lp) * 9 + lc
+/// 1       4     Dictionary size (little endian)
+/// ```
+///
+/// `lc`, `lp` and `pb` are only set through [`LzmaProperties::new`] and
+/// [`LzmaProperties::parse`], which keep them in range for the header byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LzmaProperties {
+    lc: u8,
+    lp: u8,
+    pb: u8,
+    /// Dictionary size, as stored in the header.
+    pub dict_size: u32,
+}
+
+impl LzmaProperties {
+    /// Creates properties from individual values.
+    ///
+    /// # Returns
+    /// * `Result<Self, LzmaError>` - The properties, or [`LzmaError::Unsupported`] if
+    ///   `lc`, `lp` or `pb` is out of range.
+    pub fn new(lc: u8, lp: u8, pb: u8, dict_size: u32) -> Result<Self, LzmaError> {
+        if lc > 8 || lp > 4 || pb > 4 {
+            return Err(LzmaError::Unsupported);
+        }
+        Ok(Self { lc, lp, pb, dict_size })
+    }
+
+    /// Parses the properties header using `LzmaProps_Decode`.
+    ///
+    /// # Returns
+    /// * `Result<Self, LzmaError>` - The properties, or [`LzmaError::Unsupported`] if the
+    ///   header is shorter than `LZMA_PROPS_SIZE` bytes or the first byte is invalid.
+    pub fn parse(data: &[u8]) -> Result<Self, LzmaError> {
+        let mut props = CLzmaProps::default();
+        check(unsafe { LzmaProps_Decode(&mut props, data.as_ptr(), data.len() as u32) })?;
+
+        // `LzmaProps_Decode` raises small dictionaries to the minimum, keep the raw value instead.
+        let dict_size = u32::from_le_bytes([data[1], data[2], data[3], data[4]]);
+        Ok(Self {
+            lc: props.lc,
+            lp: props.lp,
+            pb: props.pb,
+            dict_size,
+        })
+    }
+
+    /// Number of literal context bits (0-8).
+    pub fn lc(&self) -> u8 {
+        self.lc
+    }
+
+    /// Number of literal position bits (0-4).
+    pub fn lp(&self) -> u8 {
+        self.lp
+    }
+
+    /// Number of position bits (0-4).
+    pub fn pb(&self) -> u8 {
+        self.pb
+    }
+
+    /// Serializes the properties into the 5-byte header.
+    pub fn to_bytes(&self) -> [u8; LZMA_PROPS_SIZE as usize] {
+        let dict_size = self.dict_size.to_le_bytes();
+        [
+            (self.pb * 5 + self.lp) * 9 + self.lc,
+            dict_size[0],
+            dict_size[1],
+            dict_size[2],
+            dict_size[3],
+        ]
+    }
+
+    /// Returns the number of bytes the decoder allocates for these properties:
+    /// the probability model plus the dictionary buffer.
+    ///
+    /// This mirrors the allocation done by `LzmaDec_Allocate`.
+    pub fn decoder_memory_usage(&self) -> u64 {
+        let num_probs = NUM_BASE_PROBS + (LZMA_LIT_SIZE << (self.lc + self.lp));
+        // `CLzmaProb` is a `UInt16`, unless the SDK is built with `Z7_LZMA_PROB32`.
+        let probs_size = num_probs * 2;
+
+        let dict_size = self.dict_size.max(LZMA_DIC_MIN) as u64;
+        let mask: u64 = if dict_size >= 1 << 30 {
+            (1 << 22) - 1
+        } else if dict_size >= 1 << 22 {
+            (1 << 20) - 1
+        } else {
+            (1 << 12) - 1
+        };
+        let dict_buffer_size = (dict_size + mask) & !mask;
+
+        probs_size + dict_buffer_size
+    }
+}
+
+impl Default for LzmaProperties {
+    /// The common `lc=3, lp=0, pb=2` with an 8 MiB dictionary.
+    fn default() -> Self {
+        Self {
+            lc: 3,
+            lp: 0,
+            pb: 2,
+            dict_size: 1 << 23,
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for LzmaProperties {
+    type Error = LzmaError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        Self::parse(data)
+    }
+}
+
+impl TryFrom<[u8; LZMA_PROPS_SIZE as usize]> for LzmaProperties {
+    type Error = LzmaError;
+
+    fn try_from(data: [u8; LZMA_PROPS_SIZE as usize]) -> Result<Self, Self::Error> {
+        Self::parse(&data)
+    }
+}
+
+impl From<LzmaProperties> for [u8; LZMA_PROPS_SIZE as usize] {
+    fn from(props: LzmaProperties) -> Self {
+        props.to_bytes()
+    }
+}
+
+impl fmt::Display for LzmaProperties {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "lc={} lp={} pb={} dict_size={}",
+            self.lc, self.lp, self.pb, self.dict_size
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_properties_parse_and_serialize() {
+        // Properties written by `xz --format=lzma` (lc=3, lp=0, pb=2, 8 MiB dictionary).
+        let bytes = [0x5D, 0x00, 0x00, 0x80, 0x00];
+        let props = LzmaProperties::parse(&bytes).unwrap();
+        assert_eq!(props, LzmaProperties::default());
+        assert_eq!(props.to_bytes(), bytes);
+
+        let props = LzmaProperties::new(0, 4, 4, 4096).unwrap();
+        assert_eq!(LzmaProperties::try_from(props.to_bytes()), Ok(props));
+    }
+
+    #[test]
+    fn test_properties_rejects_invalid() {
+        assert_eq!(LzmaProperties::parse(&[225, 0, 0, 0, 1]), Err(LzmaError::Unsupported));
+        assert_eq!(LzmaProperties::parse(&[0x5D, 0, 0]), Err(LzmaError::Unsupported));
+        assert_eq!(LzmaProperties::new(9, 0, 0, 4096), Err(LzmaError::Unsupported));
+    }
+
+    #[test]
+    fn test_properties_match_encoder() {
+        let encoder = LzmaEncoder::with_options(&LzmaEncoderOptions::preset(1).lc(1).lp(3).pb(0)).unwrap();
+        let props = encoder.properties().unwrap();
+        assert_eq!((props.lc(), props.lp(), props.pb()), (1, 3, 0));
+
+        // (1984 + 0x300 << 4) probabilities of 2 bytes each, plus the dictionary.
+        let expected = (1984 + (0x300 << 4)) * 2 + props.dict_size as u64;
+        assert_eq!(props.decoder_memory_usage(), expected);
+        assert!(LzmaDecoder::with_properties(&props).is_ok());
+    }
+}
@@ -0,0 +1,179 @@
+use crate::buffered::{BufferedDecoder, Step, StepDecoder};
+use crate::*;
+use std::io::{self, BufRead, Read};
+
+/// Streaming LZMA decompressor implementing [`Read`] and [`BufRead`].
+///
+/// Compressed data is pulled lazily from the inner reader, so arbitrarily large
+/// streams can be decompressed without holding them in memory.
+pub struct LzmaReader<R> {
+    inner: BufferedDecoder<R, SizedLzmaDecoder>,
+}
+
+impl<R: Read> LzmaReader<R> {
+    /// Creates a reader for the classic `.lzma` format.
+    ///
+    /// The stream starts with the 5-byte properties header, followed by the 64-bit
+    /// little endian decompressed size (`u64::MAX` if unknown, in which case the
+    /// stream must end with an end marker).
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let mut props = [0u8; LZMA_PROPS_SIZE as usize];
+        inner.read_exact(&mut props)?;
+        let mut size = [0u8; 8];
+        inner.read_exact(&mut size)?;
+        let size = u64::from_le_bytes(size);
+        let unpacked_size = if size == u64::MAX { None } else { Some(size) };
+        Self::with_props(inner, &props, unpacked_size)
+    }
+
+    /// Creates a reader for a stream starting with the 5-byte properties header,
+    /// without a size field. The stream must end with an end marker.
+    pub fn without_size(mut inner: R) -> io::Result<Self> {
+        let mut props = [0u8; LZMA_PROPS_SIZE as usize];
+        inner.read_exact(&mut props)?;
+        Self::with_props(inner, &props, None)
+    }
+
+    /// Creates a reader for a raw LZMA stream whose properties are stored elsewhere.
+    ///
+    /// # Arguments
+    /// * `inner` - Reader positioned at the start of the raw LZMA stream.
+    /// * `props` - The 5-byte LZMA properties.
+    /// * `unpacked_size` - Decompressed size if known, `None` if the stream ends with an end marker.
+    pub fn with_props(inner: R, props: &[u8], unpacked_size: Option<u64>) -> io::Result<Self> {
+        let decoder = SizedLzmaDecoder {
+            decoder: LzmaDecoder::new(props)?,
+            remaining: unpacked_size,
+        };
+        Ok(Self {
+            inner: BufferedDecoder::new(inner, decoder),
+        })
+    }
+
+    /// Returns a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        self.inner.get_ref()
+    }
+
+    /// Consumes the reader, returning the inner reader.
+    ///
+    /// Note that the inner reader may have been read past the end of the LZMA stream.
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+}
+
+impl<R: Read> BufRead for LzmaReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt)
+    }
+}
+
+impl<R: Read> Read for LzmaReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+/// [`LzmaDecoder`] stopping at the decompressed size, if known.
+struct SizedLzmaDecoder {
+    decoder: LzmaDecoder,
+    remaining: Option<u64>,
+}
+
+impl StepDecoder for SizedLzmaDecoder {
+    const FORMAT: &'static str = "LZMA";
+
+    fn step(&mut self, input: &[u8], output: &mut [u8], _input_eof: bool) -> io::Result<Step> {
+        let limit = match self.remaining {
+            Some(remaining) => remaining.min(output.len() as u64) as usize,
+            None => output.len(),
+        };
+        if limit == 0 {
+            return Ok(Step {
+                consumed: 0,
+                produced: 0,
+                finished: true,
+            });
+        }
+
+        let progress = self
+            .decoder
+            .decode(input, &mut output[..limit], ELzmaFinishMode::LZMA_FINISH_ANY)?;
+        if let Some(remaining) = self.remaining.as_mut() {
+            *remaining -= progress.produced as u64;
+        }
+        if progress.is_finished() && self.remaining.is_some_and(|remaining| remaining != 0) {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "LZMA end marker found before the expected decompressed size",
+            ));
+        }
+
+        Ok(Step {
+            consumed: progress.consumed,
+            produced: progress.produced,
+            finished: progress.is_finished(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn sample_data() -> Vec<u8> {
+        (0..200_000u32).map(|x| (x % 251) as u8 ^ (x / 1024) as u8).collect()
+    }
+
+    fn compress(input: &[u8], write_end_mark: bool) -> ([u8; LZMA_PROPS_SIZE as usize], Vec<u8>) {
+        let mut encoder = LzmaEncoder::new().unwrap();
+        let props = encoder.write_properties().unwrap();
+        let mut compressed = vec![0u8; input.len() * 2];
+        let size = encoder.encode_slice(input, &mut compressed, write_end_mark).unwrap();
+        compressed.truncate(size);
+        (props, compressed)
+    }
+
+    #[test]
+    fn test_reader_with_known_size() {
+        let input = sample_data();
+        let (props, compressed) = compress(&input, false);
+
+        let mut file = props.to_vec();
+        file.extend_from_slice(&(input.len() as u64).to_le_bytes());
+        file.extend_from_slice(&compressed);
+
+        let mut output = Vec::new();
+        LzmaReader::new(Cursor::new(file)).unwrap().read_to_end(&mut output).unwrap();
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn test_reader_with_end_marker() {
+        let input = sample_data();
+        let (props, compressed) = compress(&input, true);
+
+        let mut file = props.to_vec();
+        file.extend_from_slice(&compressed);
+
+        let mut output = Vec::new();
+        LzmaReader::without_size(Cursor::new(file)).unwrap().read_to_end(&mut output).unwrap();
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn test_reader_truncated_stream() {
+        let input = sample_data();
+        let (props, compressed) = compress(&input, true);
+
+        let reader = LzmaReader::with_props(Cursor::new(&compressed[..compressed.len() / 2]), &props, None);
+        let err = reader.unwrap().read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,256 @@
+use crate::crc::init_tables;
+use crate::streams::LookInStream;
+use crate::*;
+use core::{ptr, slice};
+use std::io::{self, Read, Seek, Write};
+use std::time::{Duration, SystemTime};
+
+/// Number of 100ns intervals between 1601-01-01 (NTFS epoch) and 1970-01-01 (Unix epoch).
+const NTFS_TO_UNIX_EPOCH: u64 = 116_444_736_000_000_000;
+
+/// Metadata of a single file or directory in a [`SevenZipArchive`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SevenZipEntry {
+    /// Path of the entry inside the archive, as stored.
+    pub name: String,
+    /// Decompressed size in bytes.
+    pub size: u64,
+    /// True if the entry is a directory.
+    pub is_dir: bool,
+    /// Windows file attributes, if stored.
+    pub attributes: Option<u32>,
+    /// Modification time as an NTFS `FILETIME` (100ns intervals since 1601-01-01), if stored.
+    pub mtime: Option<u64>,
+    /// CRC32 of the decompressed data, if stored.
+    pub crc: Option<u32>,
+    /// Index of the folder (solid block) holding the data, `None` for directories and empty files.
+    pub folder_index: Option<u32>,
+}
+
+impl SevenZipEntry {
+    /// Returns the modification time, if stored and representable as a [`SystemTime`].
+    pub fn modified(&self) -> Option<SystemTime> {
+        let mtime = self.mtime?;
+        let since_unix = Duration::from_nanos(mtime.checked_sub(NTFS_TO_UNIX_EPOCH)?.checked_mul(100)?);
+        SystemTime::UNIX_EPOCH.checked_add(since_unix)
+    }
+}
+
+/// Read-only `.7z` archive over `CSzArEx`.
+///
+/// Entries of a solid block are decompressed together; the last decompressed block is
+/// cached, so extracting entries in order only decompresses each block once.
+pub struct SevenZipArchive<R: Read + Seek> {
+    db: CSzArEx,
+    stream: LookInStream<R>,
+    alloc: Allocator,
+    entries: Vec<SevenZipEntry>,
+    // Cache of the last decompressed folder, managed by `SzArEx_Extract`.
+    block_index: u32,
+    out_buffer: *mut Byte,
+    out_buffer_size: usize,
+}
+
+// The archive state is exclusively owned and only ever accessed through `&mut self`.
+unsafe impl<R: Read + Seek + Send> Send for SevenZipArchive<R> {}
+
+impl<R: Read + Seek> SevenZipArchive<R> {
+    /// Opens an archive and reads its list of entries.
+    ///
+    /// # Returns
+    /// * `io::Result<Self>` - The archive, or an [`io::ErrorKind::InvalidData`] error if `reader`
+    ///   does not contain a valid `.7z` archive.
+    pub fn open(reader: R) -> io::Result<Self> {
+        init_tables();
+
+        let mut archive = Self {
+            db: CSzArEx::default(),
+            stream: LookInStream::new(reader),
+            alloc: Allocator::default(),
+            entries: Vec::new(),
+            block_index: u32::MAX,
+            out_buffer: ptr::null_mut(),
+            out_buffer_size: 0,
+        };
+
+        unsafe { SzArEx_Init(&mut archive.db) };
+        let res = unsafe {
+            SzArEx_Open(
+                &mut archive.db,
+                archive.stream.as_ptr(),
+                archive.alloc.as_ref(),
+                archive.alloc.as_ref(),
+            )
+        };
+        if let Some(e) = archive.stream.take_error() {
+            return Err(e);
+        }
+        check(res)?;
+
+        archive.entries = (0..archive.db.NumFiles).map(|index| archive.read_entry(index)).collect();
+        Ok(archive)
+    }
+
+    /// Returns all entries, in archive order.
+    pub fn entries(&self) -> &[SevenZipEntry] {
+        &self.entries
+    }
+
+    /// Returns the index of the entry with the given name.
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.name == name)
+    }
+
+    /// Extracts the entry at `index` into a newly allocated buffer.
+    pub fn extract_to_vec(&mut self, index: usize) -> io::Result<Vec<u8>> {
+        Ok(self.extract(index)?.to_vec())
+    }
+
+    /// Extracts the entry at `index` into `output`.
+    pub fn extract_to<W: Write>(&mut self, index: usize, output: &mut W) -> io::Result<()> {
+        output.write_all(self.extract(index)?)
+    }
+
+    /// Decompresses the folder holding the entry (unless cached) and returns the entry's data.
+    fn extract(&mut self, index: usize) -> io::Result<&[u8]> {
+        if index >= self.entries.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "7z entry index out of range"));
+        }
+
+        let mut offset = 0;
+        let mut size = 0;
+        let res = unsafe {
+            SzArEx_Extract(
+                &self.db,
+                self.stream.as_ptr(),
+                index as u32,
+                &mut self.block_index,
+                &mut self.out_buffer,
+                &mut self.out_buffer_size,
+                &mut offset,
+                &mut size,
+                self.alloc.as_ref(),
+                self.alloc.as_ref(),
+            )
+        };
+        if let Some(e) = self.stream.take_error() {
+            return Err(e);
+        }
+        check(res)?;
+
+        if size == 0 {
+            return Ok(&[]);
+        }
+        Ok(unsafe { slice::from_raw_parts(self.out_buffer.add(offset), size) })
+    }
+
+    fn read_entry(&self, index: u32) -> SevenZipEntry {
+        let i = index as usize;
+        let db = &self.db;
+
+        // The length includes the null terminator.
+        let len = unsafe { SzArEx_GetFileNameUtf16(db, i, ptr::null_mut()) };
+        let mut name = vec![0u16; len];
+        unsafe { SzArEx_GetFileNameUtf16(db, i, name.as_mut_ptr()) };
+        name.truncate(len.saturating_sub(1));
+
+        unsafe {
+            let folder_index = *db.FileToFolder.add(i);
+            SevenZipEntry {
+                name: String::from_utf16_lossy(&name),
+                size: *db.UnpackPositions.add(i + 1) - *db.UnpackPositions.add(i),
+                is_dir: bit_is_set(db.IsDirs, i),
+                attributes: bit_is_set(db.Attribs.Defs, i).then(|| *db.Attribs.Vals.add(i)),
+                mtime: bit_is_set(db.MTime.Defs, i).then(|| {
+                    let time = *db.MTime.Vals.add(i);
+                    (time.High as u64) << 32 | time.Low as u64
+                }),
+                crc: bit_is_set(db.CRCs.Defs, i).then(|| *db.CRCs.Vals.add(i)),
+                folder_index: (folder_index != u32::MAX).then_some(folder_index),
+            }
+        }
+    }
+}
+
+impl<R: Read + Seek> Drop for SevenZipArchive<R> {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some(free) = self.alloc.as_ref().Free {
+                free(self.alloc.as_ref(), self.out_buffer as *mut c_void);
+            }
+            SzArEx_Free(&mut self.db, self.alloc.as_ref());
+        }
+    }
+}
+
+/// Checks bit `i` of an SDK bit array, which may be null if no bits are set
+/// (`SzBitArray_Check`/`SzBitWithVals_Check`).
+unsafe fn bit_is_set(bits: *const Byte, i: usize) -> bool {
+    !bits.is_null() && (*bits.add(i >> 3) & (0x80 >> (i & 7))) != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// A solid LZMA archive (written by a small script following the 7z format
+    /// documentation) containing `hello.txt`, `dir/`, `dir/data.bin` and the empty `empty.txt`.
+    const ARCHIVE: &[u8] = include_bytes!("../tests/fixtures/archive.7z");
+
+    fn data_bin() -> Vec<u8> {
+        (0..3000u32).map(|i| (i * 7 % 256) as u8).collect()
+    }
+
+    #[test]
+    fn test_seven_zip_entries() {
+        let archive = SevenZipArchive::open(Cursor::new(ARCHIVE)).unwrap();
+        let names: Vec<&str> = archive.entries().iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["hello.txt", "dir", "dir/data.bin", "empty.txt"]);
+
+        let hello = &archive.entries()[0];
+        assert_eq!(hello.size, 75);
+        assert!(!hello.is_dir);
+        assert_eq!(hello.attributes, Some(0x20));
+        assert_eq!(hello.mtime, Some(133_000_000_000_000_000));
+        assert_eq!(hello.folder_index, Some(0));
+        assert!(hello.modified().is_some());
+
+        let dir = &archive.entries()[1];
+        assert!(dir.is_dir);
+        assert_eq!((dir.size, dir.crc, dir.folder_index), (0, None, None));
+
+        let empty = &archive.entries()[3];
+        assert!(!empty.is_dir);
+        assert_eq!((empty.size, empty.folder_index), (0, None));
+    }
+
+    #[test]
+    fn test_seven_zip_extract() {
+        let mut archive = SevenZipArchive::open(Cursor::new(ARCHIVE)).unwrap();
+
+        let data = archive.extract_to_vec(archive.find("dir/data.bin").unwrap()).unwrap();
+        assert_eq!(data, data_bin());
+        assert_eq!(archive.entries()[2].crc, Some(Crc32::checksum(&data)));
+
+        // Same folder, served from the cached block.
+        let mut hello = Vec::new();
+        archive.extract_to(0, &mut hello).unwrap();
+        assert_eq!(hello, b"Hello from a 7z archive!\n".repeat(3));
+
+        assert!(archive.extract_to_vec(3).unwrap().is_empty());
+        assert_eq!(archive.extract_to_vec(4).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_seven_zip_rejects_bad_input() {
+        let err = SevenZipArchive::open(Cursor::new(b"definitely not a 7z archive".to_vec())).err();
+        assert_eq!(err.unwrap().kind(), io::ErrorKind::InvalidData);
+
+        // Corrupt the packed stream, the stored CRCs no longer match.
+        let mut corrupt = ARCHIVE.to_vec();
+        corrupt[40] ^= 0x55;
+        let mut archive = SevenZipArchive::open(Cursor::new(corrupt)).unwrap();
+        assert!(archive.extract_to_vec(0).is_err());
+    }
+}
@@ -0,0 +1,575 @@
+use crate::sha::Sha256;
+use crate::*;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::{fmt, ptr};
+#[cfg(feature = "std")]
+use crate::buffered::{BufferedDecoder, Step, StepDecoder};
+#[cfg(feature = "std")]
+use std::io::{self, BufRead, Read, Write};
+
+/// Number of key derivation cycles (as a power of two) used by 7-Zip when creating archives.
+pub const SEVEN_ZIP_AES_CYCLES_DEFAULT: u32 = 19;
+
+/// Largest number of key derivation cycles (as a power of two) accepted by 7-Zip.
+pub const SEVEN_ZIP_AES_CYCLES_MAX: u32 = 24;
+
+/// Special cycle count meaning the key is the salt and password bytes, without hashing.
+pub const SEVEN_ZIP_AES_CYCLES_NO_HASH: u32 = 0x3F;
+
+/// Largest salt and IV size in bytes.
+pub const SEVEN_ZIP_AES_SALT_MAX: usize = 16;
+
+/// Number of derived keys kept by [`SevenZipAesKeyCache`], as in 7-Zip.
+const KEY_CACHE_SIZE: usize = 32;
+
+/// AES-256 key derived from a password with the `7zAES` scheme.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SevenZipAesKey([u8; AES256_KEY_SIZE]);
+
+impl SevenZipAesKey {
+    /// Derives the key for `password`.
+    ///
+    /// The salt, then the UTF-16LE password, then a 64-bit little endian round counter are
+    /// hashed with SHA-256 for `2^num_cycles_power` rounds.
+    ///
+    /// # Returns
+    /// * `Result<Self, LzmaError>` - The key, or [`LzmaError::Param`] if `salt` is longer than
+    ///   [`SEVEN_ZIP_AES_SALT_MAX`] or `num_cycles_power` is above [`SEVEN_ZIP_AES_CYCLES_MAX`]
+    ///   (other than [`SEVEN_ZIP_AES_CYCLES_NO_HASH`]).
+    pub fn derive(password: &str, salt: &[u8], num_cycles_power: u32) -> Result<Self, LzmaError> {
+        Self::derive_utf16(&Utf16Password::new(password).0, salt, num_cycles_power)
+    }
+
+    fn derive_utf16(password: &[u8], salt: &[u8], num_cycles_power: u32) -> Result<Self, LzmaError> {
+        if salt.len() > SEVEN_ZIP_AES_SALT_MAX
+            || (num_cycles_power > SEVEN_ZIP_AES_CYCLES_MAX && num_cycles_power != SEVEN_ZIP_AES_CYCLES_NO_HASH)
+        {
+            return Err(LzmaError::Param);
+        }
+
+        let mut key = [0u8; AES256_KEY_SIZE];
+        if num_cycles_power == SEVEN_ZIP_AES_CYCLES_NO_HASH {
+            for (dst, src) in key.iter_mut().zip(salt.iter().chain(password)) {
+                *dst = *src;
+            }
+            return Ok(Self(key));
+        }
+
+        // Hash `salt || password || counter` per round, bumping the counter in place.
+        let mut round = Vec::with_capacity(salt.len() + password.len() + 8);
+        round.extend_from_slice(salt);
+        round.extend_from_slice(password);
+        let counter_pos = round.len();
+        round.extend_from_slice(&[0; 8]);
+
+        let mut sha = Sha256::new();
+        for counter in 0..1u64 << num_cycles_power {
+            round[counter_pos..].copy_from_slice(&counter.to_le_bytes());
+            sha.update(&round);
+        }
+        wipe(&mut round);
+        key.copy_from_slice(&sha.finalize());
+        Ok(Self(key))
+    }
+
+    /// Returns the raw AES-256 key.
+    pub fn as_bytes(&self) -> &[u8; AES256_KEY_SIZE] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for SevenZipAesKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SevenZipAesKey(..)")
+    }
+}
+
+impl Drop for SevenZipAesKey {
+    fn drop(&mut self) {
+        wipe(&mut self.0);
+    }
+}
+
+/// Overwrites `bytes` with zeros, without the stores being optimized away.
+fn wipe(bytes: &mut [u8]) {
+    for byte in bytes.iter_mut() {
+        unsafe { ptr::write_volatile(byte, 0) };
+    }
+}
+
+/// UTF-16LE encoded password, wiped when dropped.
+struct Utf16Password(Vec<u8>);
+
+impl Utf16Password {
+    fn new(password: &str) -> Self {
+        // There are at most as many UTF-16 units as UTF-8 bytes, so this never reallocates
+        // and leaves no copies behind.
+        let mut bytes = Vec::with_capacity(password.len() * 2);
+        bytes.extend(password.encode_utf16().flat_map(u16::to_le_bytes));
+        Self(bytes)
+    }
+}
+
+impl Drop for Utf16Password {
+    fn drop(&mut self) {
+        wipe(&mut self.0);
+    }
+}
+
+struct CachedKey {
+    password: Utf16Password,
+    salt: Vec<u8>,
+    num_cycles_power: u32,
+    // Boxed, so moving entries around doesn't leave copies of the key behind.
+    key: Box<SevenZipAesKey>,
+}
+
+/// Cache of derived [`SevenZipAesKey`]s.
+///
+/// Key derivation is deliberately slow, and all entries of an archive usually share the
+/// password and salt, so this avoids repeating it for each entry. The most recently used
+/// keys are kept. Keys and passwords are wiped from memory when evicted or dropped.
+#[derive(Default)]
+pub struct SevenZipAesKeyCache {
+    // Most recently used first.
+    entries: Vec<CachedKey>,
+}
+
+impl SevenZipAesKeyCache {
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the cached key for these parameters, deriving and caching it if needed.
+    /// See [`SevenZipAesKey::derive`].
+    pub fn derive(&mut self, password: &str, salt: &[u8], num_cycles_power: u32) -> Result<SevenZipAesKey, LzmaError> {
+        let password = Utf16Password::new(password);
+        let found = self.entries.iter().position(|entry| {
+            entry.password.0 == password.0 && entry.salt == salt && entry.num_cycles_power == num_cycles_power
+        });
+
+        let entry = match found {
+            Some(index) => self.entries.remove(index),
+            None => {
+                let key = SevenZipAesKey::derive_utf16(&password.0, salt, num_cycles_power)?;
+                self.entries.truncate(KEY_CACHE_SIZE - 1);
+                CachedKey {
+                    password,
+                    salt: salt.to_vec(),
+                    num_cycles_power,
+                    key: Box::new(key),
+                }
+            }
+        };
+
+        let key = SevenZipAesKey::clone(&entry.key);
+        self.entries.insert(0, entry);
+        Ok(key)
+    }
+
+    /// Returns the number of cached keys.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if no keys are cached.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Removes all cached keys.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Coder properties of the `7zAES` method, as stored in `.7z` archives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SevenZipAesProperties {
+    /// Key derivation cycles as a power of two.
+    pub num_cycles_power: u32,
+    /// Salt, up to [`SEVEN_ZIP_AES_SALT_MAX`] bytes (7-Zip writes none).
+    pub salt: Vec<u8>,
+    /// CBC IV, up to 16 bytes and zero-padded to a full block.
+    pub iv: Vec<u8>,
+}
+
+impl SevenZipAesProperties {
+    /// Parses the coder properties, like `7zAes.cpp`.
+    ///
+    /// # Returns
+    /// * `Result<Self, LzmaError>` - The properties, or [`LzmaError::Unsupported`] if they
+    ///   are malformed or use more cycles than 7-Zip accepts.
+    pub fn parse(props: &[u8]) -> Result<Self, LzmaError> {
+        let Some(&b0) = props.first() else {
+            return Ok(Self {
+                num_cycles_power: 0,
+                salt: Vec::new(),
+                iv: Vec::new(),
+            });
+        };
+
+        let num_cycles_power = (b0 & 0x3F) as u32;
+        let (salt, iv) = if b0 & 0xC0 == 0 {
+            if props.len() != 1 {
+                return Err(LzmaError::Unsupported);
+            }
+            (&[][..], &[][..])
+        } else {
+            let b1 = *props.get(1).ok_or(LzmaError::Unsupported)?;
+            let salt_size = ((b0 >> 7) & 1) as usize + (b1 >> 4) as usize;
+            let iv_size = ((b0 >> 6) & 1) as usize + (b1 & 0x0F) as usize;
+            if props.len() != 2 + salt_size + iv_size {
+                return Err(LzmaError::Unsupported);
+            }
+            props[2..].split_at(salt_size)
+        };
+
+        if num_cycles_power > SEVEN_ZIP_AES_CYCLES_MAX && num_cycles_power != SEVEN_ZIP_AES_CYCLES_NO_HASH {
+            return Err(LzmaError::Unsupported);
+        }
+        Ok(Self {
+            num_cycles_power,
+            salt: salt.to_vec(),
+            iv: iv.to_vec(),
+        })
+    }
+
+    /// Serializes the coder properties.
+    ///
+    /// # Returns
+    /// * `Result<Vec<u8>, LzmaError>` - The properties, or [`LzmaError::Param`] if the salt or
+    ///   IV is longer than 16 bytes or `num_cycles_power` doesn't fit.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, LzmaError> {
+        if self.salt.len() > SEVEN_ZIP_AES_SALT_MAX
+            || self.iv.len() > AES_BLOCK_SIZE as usize
+            || self.num_cycles_power > SEVEN_ZIP_AES_CYCLES_NO_HASH
+        {
+            return Err(LzmaError::Param);
+        }
+
+        let mut b0 = self.num_cycles_power as u8;
+        if !self.salt.is_empty() {
+            b0 |= 1 << 7;
+        }
+        if !self.iv.is_empty() {
+            b0 |= 1 << 6;
+        }
+
+        let mut props = alloc::vec![b0];
+        if !self.salt.is_empty() || !self.iv.is_empty() {
+            let salt_bits = self.salt.len().saturating_sub(1) as u8;
+            let iv_bits = self.iv.len().saturating_sub(1) as u8;
+            props.push(salt_bits << 4 | iv_bits);
+            props.extend_from_slice(&self.salt);
+            props.extend_from_slice(&self.iv);
+        }
+        Ok(props)
+    }
+
+    /// Returns the IV zero-padded to a full block, as used by the cipher.
+    pub fn iv_block(&self) -> [u8; AES_BLOCK_SIZE as usize] {
+        let mut block = [0u8; AES_BLOCK_SIZE as usize];
+        let len = self.iv.len().min(block.len());
+        block[..len].copy_from_slice(&self.iv[..len]);
+        block
+    }
+}
+
+/// Amount of plaintext buffered before it is encrypted, a multiple of the block size.
+#[cfg(feature = "std")]
+const PENDING_SIZE: usize = 4096;
+
+/// Encrypts data written to it with `7zAES` (AES-256-CBC) into the inner writer.
+///
+/// The last block is padded with zeros by [`SevenZipAesWriter::finish`]; readers of the
+/// archive rely on the stored unpacked size of the next coder to drop the padding.
+/// Dropping the writer writes the last block too, ignoring errors. Buffered plaintext
+/// is wiped once it has been encrypted.
+#[cfg(feature = "std")]
+pub struct SevenZipAesWriter<W: Write> {
+    // Taken once the last block is written.
+    inner: Option<W>,
+    cipher: Aes256Cbc,
+    // Never reallocated, so no copies of the plaintext are left behind.
+    pending: Vec<u8>,
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> SevenZipAesWriter<W> {
+    /// Creates a writer encrypting with `key` and the IV from `props`.
+    pub fn new(inner: W, key: &SevenZipAesKey, props: &SevenZipAesProperties) -> Self {
+        Self {
+            inner: Some(inner),
+            cipher: Aes256Cbc::encryptor(key.as_bytes(), &props.iv_block()),
+            pending: Vec::with_capacity(PENDING_SIZE),
+        }
+    }
+
+    /// Pads and writes the last block, returning the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.finish_blocks()?;
+        self.inner.take().ok_or_else(stream_finished)
+    }
+
+    fn finish_blocks(&mut self) -> io::Result<()> {
+        let padded = self.pending.len().next_multiple_of(AES_BLOCK_SIZE as usize);
+        self.pending.resize(padded, 0);
+        self.write_blocks(padded)?;
+        match self.inner.as_mut() {
+            Some(inner) => inner.flush(),
+            None => Err(stream_finished()),
+        }
+    }
+
+    /// Encrypts and writes the first `len` pending bytes, a multiple of the block size.
+    fn write_blocks(&mut self, len: usize) -> io::Result<()> {
+        let Some(inner) = self.inner.as_mut() else {
+            return Err(stream_finished());
+        };
+        let written = self
+            .cipher
+            .process(&mut self.pending[..len])
+            .map_err(io::Error::from)
+            .and_then(|()| inner.write_all(&self.pending[..len]));
+        if let Err(e) = written {
+            // The stream is broken, later calls fail instead of writing more output.
+            wipe(&mut self.pending);
+            self.pending.clear();
+            self.inner = None;
+            return Err(e);
+        }
+
+        // Move the partial block to the front and wipe the plaintext left behind it.
+        let rest = self.pending.len() - len;
+        self.pending.copy_within(len.., 0);
+        wipe(&mut self.pending[rest..]);
+        self.pending.truncate(rest);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+fn stream_finished() -> io::Error {
+    io::Error::other("7zAES stream already finished")
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> Write for SevenZipAesWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let count = buf.len().min(PENDING_SIZE - self.pending.len());
+        self.pending.extend_from_slice(&buf[..count]);
+        let whole = self.pending.len() - self.pending.len() % AES_BLOCK_SIZE as usize;
+        if whole > 0 {
+            self.write_blocks(whole)?;
+        }
+        Ok(count)
+    }
+
+    /// Flushes the inner writer. A trailing partial block is only written by [`SevenZipAesWriter::finish`].
+    fn flush(&mut self) -> io::Result<()> {
+        match self.inner.as_mut() {
+            Some(inner) => inner.flush(),
+            None => Err(stream_finished()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> Drop for SevenZipAesWriter<W> {
+    fn drop(&mut self) {
+        // Writing while unwinding could panic again, just wipe the plaintext then.
+        if self.inner.is_some() && !std::thread::panicking() {
+            let _ = self.finish_blocks();
+        }
+        wipe(&mut self.pending);
+    }
+}
+
+/// Decrypts `7zAES` (AES-256-CBC) data from the inner reader.
+///
+/// Yields the zero padding of the last block too; stop reading at the unpacked size
+/// stored in the archive, or let the next decoder (e.g. [`LzmaReader`]) stop at its end.
+#[cfg(feature = "std")]
+pub struct SevenZipAesReader<R> {
+    inner: BufferedDecoder<R, Aes256Cbc>,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> SevenZipAesReader<R> {
+    /// Creates a reader decrypting with `key` and the IV from `props`.
+    pub fn new(inner: R, key: &SevenZipAesKey, props: &SevenZipAesProperties) -> Self {
+        let cipher = Aes256Cbc::decryptor(key.as_bytes(), &props.iv_block());
+        Self {
+            inner: BufferedDecoder::new(inner, cipher),
+        }
+    }
+
+    /// Returns a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        self.inner.get_ref()
+    }
+
+    /// Consumes the reader, returning the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> BufRead for SevenZipAesReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Read for SevenZipAesReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+#[cfg(feature = "std")]
+impl StepDecoder for Aes256Cbc {
+    const FORMAT: &'static str = "7zAES";
+
+    fn step(&mut self, input: &[u8], output: &mut [u8], input_eof: bool) -> io::Result<Step> {
+        // Only whole blocks can be decrypted, wait for more input otherwise.
+        let len = input.len().min(output.len()) / AES_BLOCK_SIZE as usize * AES_BLOCK_SIZE as usize;
+        if len == 0 && input_eof && !input.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "7zAES data is not a multiple of the block size",
+            ));
+        }
+
+        output[..len].copy_from_slice(&input[..len]);
+        self.process(&mut output[..len])?;
+        Ok(Step {
+            consumed: len,
+            produced: len,
+            finished: input_eof && input.is_empty(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_seven_zip_aes_key_derivation() {
+        let key = SevenZipAesKey::derive("password", &[], SEVEN_ZIP_AES_CYCLES_DEFAULT).unwrap();
+        assert_eq!(key.as_bytes()[..], hex("97bc6e1f9adb6f6a2507fe6657ee8854b7091a0a117b746421b839210f5d7eef"));
+
+        let salt: Vec<u8> = (0..16).collect();
+        let key = SevenZipAesKey::derive("Pässwörd", &salt, 6).unwrap();
+        assert_eq!(key.as_bytes()[..], hex("bdbecfe1c2b34c97714dba7e08121dd9f76b2531de89011f78f301599c31dc74"));
+
+        let key = SevenZipAesKey::derive("secret", &[1, 2, 3], SEVEN_ZIP_AES_CYCLES_NO_HASH).unwrap();
+        assert_eq!(key.as_bytes()[..], hex("0102037300650063007200650074000000000000000000000000000000000000"));
+
+        assert_eq!(SevenZipAesKey::derive("x", &[0; 17], 1), Err(LzmaError::Param));
+        assert_eq!(SevenZipAesKey::derive("x", &[], 25), Err(LzmaError::Param));
+    }
+
+    #[test]
+    fn test_seven_zip_aes_key_cache() {
+        let mut cache = SevenZipAesKeyCache::new();
+        let first = cache.derive("password", &[1, 2], 4).unwrap();
+        assert_eq!(cache.derive("password", &[1, 2], 4).unwrap(), first);
+        assert_eq!(cache.len(), 1);
+
+        assert_ne!(cache.derive("password", &[1, 3], 4).unwrap(), first);
+        assert_ne!(cache.derive("Password", &[1, 2], 4).unwrap(), first);
+        assert_eq!(cache.len(), 3);
+
+        for salt in 0..40u8 {
+            cache.derive("password", &[salt], 1).unwrap();
+        }
+        assert_eq!(cache.len(), KEY_CACHE_SIZE);
+    }
+
+    #[test]
+    fn test_seven_zip_aes_properties() {
+        let props = SevenZipAesProperties {
+            num_cycles_power: SEVEN_ZIP_AES_CYCLES_DEFAULT,
+            salt: Vec::new(),
+            iv: (0..16).collect(),
+        };
+        let bytes = props.to_bytes().unwrap();
+        assert_eq!(bytes[..2], [0x40 | 19, 0x0F]);
+        assert_eq!(SevenZipAesProperties::parse(&bytes).unwrap(), props);
+
+        assert_eq!(SevenZipAesProperties::parse(&[0x40 | 19, 0x0F, 0]), Err(LzmaError::Unsupported));
+        assert_eq!(SevenZipAesProperties::parse(&[30]), Err(LzmaError::Unsupported));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_seven_zip_aes_stream_round_trip() {
+        let key = SevenZipAesKey::derive("password", &[], SEVEN_ZIP_AES_CYCLES_DEFAULT).unwrap();
+        let props = SevenZipAesProperties {
+            num_cycles_power: SEVEN_ZIP_AES_CYCLES_DEFAULT,
+            salt: Vec::new(),
+            iv: (0..8).collect(),
+        };
+        let data = b"7z AES payload!\n7z AES payload!\ntail";
+
+        let mut writer = SevenZipAesWriter::new(Vec::new(), &key, &props);
+        for chunk in data.chunks(5) {
+            writer.write_all(chunk).unwrap();
+        }
+        let encrypted = writer.finish().unwrap();
+        // Reference ciphertext from an independent AES-256-CBC implementation.
+        assert_eq!(
+            encrypted,
+            hex("50d034a886dfe46a85c57066e10ea238e77848a34b5e66386575ed9f89f408c9b7f1b00795d8611f33fc8cb9866d7da5")
+        );
+
+        let mut decrypted = Vec::new();
+        SevenZipAesReader::new(io::Cursor::new(&encrypted), &key, &props)
+            .read_to_end(&mut decrypted)
+            .unwrap();
+        assert_eq!(decrypted[..data.len()], data[..]);
+        assert!(decrypted[data.len()..].iter().all(|&b| b == 0));
+
+        let mut truncated = SevenZipAesReader::new(io::Cursor::new(&encrypted[..40]), &key, &props);
+        let err = truncated.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_seven_zip_aes_writer_finishes_on_drop() {
+        let key = SevenZipAesKey::derive("password", &[], SEVEN_ZIP_AES_CYCLES_DEFAULT).unwrap();
+        let props = SevenZipAesProperties {
+            num_cycles_power: SEVEN_ZIP_AES_CYCLES_DEFAULT,
+            salt: Vec::new(),
+            iv: (0..8).collect(),
+        };
+        let data: Vec<u8> = (0..10_000u32).map(|x| x as u8).collect();
+
+        let mut writer = SevenZipAesWriter::new(Vec::new(), &key, &props);
+        writer.write_all(&data).unwrap();
+        let finished = writer.finish().unwrap();
+
+        let mut dropped = Vec::new();
+        let mut writer = SevenZipAesWriter::new(&mut dropped, &key, &props);
+        writer.write_all(&data).unwrap();
+        drop(writer);
+        assert_eq!(dropped, finished);
+        assert_eq!(dropped.len(), data.len().next_multiple_of(AES_BLOCK_SIZE as usize));
+    }
+}
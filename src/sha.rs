@@ -0,0 +1,357 @@
+use crate::once::InitOnce;
+use crate::*;
+
+/// Size of a SHA-256 digest in bytes.
+pub const SHA256_DIGEST_LEN: usize = SHA256_DIGEST_SIZE as usize;
+
+/// Size of a SHA-1 digest in bytes.
+pub const SHA1_DIGEST_LEN: usize = SHA1_DIGEST_SIZE as usize;
+
+static PREPARE: InitOnce = InitOnce::new();
+
+/// Detects the SHA instructions of the CPU (`Sha256Prepare`, `Sha1Prepare`), exactly once.
+fn prepare() {
+    PREPARE.call(|| unsafe {
+        Sha256Prepare();
+        Sha1Prepare();
+    });
+}
+
+/// Block function used by [`Sha256`] and [`Sha1`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShaImplementation {
+    /// The fastest one supported by the CPU.
+    #[default]
+    Auto,
+    /// Portable C code.
+    Software,
+    /// SHA-NI on x86, or the ARMv8 crypto extensions.
+    Hardware,
+}
+
+impl ShaImplementation {
+    fn algo(self) -> u32 {
+        // The SHA1_ALGO_* values are the same.
+        match self {
+            Self::Auto => SHA256_ALGO_DEFAULT,
+            Self::Software => SHA256_ALGO_SW,
+            Self::Hardware => SHA256_ALGO_HW,
+        }
+    }
+}
+
+/// Returns true if SHA-256 runs on hardware instructions (SHA-NI, ARMv8 crypto extensions).
+pub fn sha256_hardware_accelerated() -> bool {
+    Sha256::with_implementation(ShaImplementation::Hardware).is_ok()
+}
+
+/// Returns true if SHA-1 runs on hardware instructions (SHA-NI, ARMv8 crypto extensions).
+pub fn sha1_hardware_accelerated() -> bool {
+    Sha1::with_implementation(ShaImplementation::Hardware).is_ok()
+}
+
+/// Streaming SHA-256 over `Sha256.c`.
+#[derive(Clone)]
+pub struct Sha256 {
+    sha: CSha256,
+}
+
+// The state is exclusively owned and only ever accessed through `&mut self`.
+unsafe impl Send for Sha256 {}
+
+impl Sha256 {
+    /// Creates a hasher using the fastest implementation for the CPU.
+    pub fn new() -> Self {
+        prepare();
+        let mut sha = CSha256::default();
+        unsafe { Sha256_Init(&mut sha) };
+        Self { sha }
+    }
+
+    /// Creates a hasher using the given implementation (`Sha256_SetFunction`).
+    ///
+    /// # Returns
+    /// * `Result<Self, LzmaError>` - The hasher, or [`LzmaError::Unsupported`] if the CPU
+    ///   lacks the instructions for [`ShaImplementation::Hardware`].
+    pub fn with_implementation(implementation: ShaImplementation) -> Result<Self, LzmaError> {
+        let mut hasher = Self::new();
+        if unsafe { Sha256_SetFunction(&mut hasher.sha, implementation.algo()) } == 0 {
+            return Err(LzmaError::Unsupported);
+        }
+        Ok(hasher)
+    }
+
+    /// Returns the SHA-256 digest of `data`.
+    pub fn digest(data: &[u8]) -> [u8; SHA256_DIGEST_LEN] {
+        let mut hasher = Self::new();
+        hasher.update(data);
+        hasher.finalize()
+    }
+
+    /// Hashes the next part of the message.
+    pub fn update(&mut self, data: &[u8]) {
+        unsafe { Sha256_Update(&mut self.sha, data.as_ptr(), data.len()) };
+    }
+
+    /// Returns the digest of the message.
+    pub fn finalize(mut self) -> [u8; SHA256_DIGEST_LEN] {
+        self.finalize_reset()
+    }
+
+    /// Returns the digest of the message and resets the hasher for a new one.
+    pub fn finalize_reset(&mut self) -> [u8; SHA256_DIGEST_LEN] {
+        let mut digest = [0u8; SHA256_DIGEST_LEN];
+        // `Sha256_Final` also reinitializes the state.
+        unsafe { Sha256_Final(&mut self.sha, digest.as_mut_ptr()) };
+        digest
+    }
+
+    /// Discards the hashed data, keeping the selected implementation.
+    pub fn reset(&mut self) {
+        unsafe { Sha256_InitState(&mut self.sha) };
+    }
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Streaming SHA-1 over `Sha1.c`.
+#[derive(Clone)]
+pub struct Sha1 {
+    sha: CSha1,
+}
+
+// The state is exclusively owned and only ever accessed through `&mut self`.
+unsafe impl Send for Sha1 {}
+
+impl Sha1 {
+    /// Creates a hasher using the fastest implementation for the CPU.
+    pub fn new() -> Self {
+        prepare();
+        let mut sha = CSha1::default();
+        unsafe { Sha1_Init(&mut sha) };
+        Self { sha }
+    }
+
+    /// Creates a hasher using the given implementation (`Sha1_SetFunction`).
+    ///
+    /// # Returns
+    /// * `Result<Self, LzmaError>` - The hasher, or [`LzmaError::Unsupported`] if the CPU
+    ///   lacks the instructions for [`ShaImplementation::Hardware`].
+    pub fn with_implementation(implementation: ShaImplementation) -> Result<Self, LzmaError> {
+        let mut hasher = Self::new();
+        if unsafe { Sha1_SetFunction(&mut hasher.sha, implementation.algo()) } == 0 {
+            return Err(LzmaError::Unsupported);
+        }
+        Ok(hasher)
+    }
+
+    /// Returns the SHA-1 digest of `data`.
+    pub fn digest(data: &[u8]) -> [u8; SHA1_DIGEST_LEN] {
+        let mut hasher = Self::new();
+        hasher.update(data);
+        hasher.finalize()
+    }
+
+    /// Hashes the next part of the message.
+    pub fn update(&mut self, data: &[u8]) {
+        unsafe { Sha1_Update(&mut self.sha, data.as_ptr(), data.len()) };
+    }
+
+    /// Returns the digest of the message.
+    pub fn finalize(mut self) -> [u8; SHA1_DIGEST_LEN] {
+        self.finalize_reset()
+    }
+
+    /// Returns the digest of the message and resets the hasher for a new one.
+    pub fn finalize_reset(&mut self) -> [u8; SHA1_DIGEST_LEN] {
+        let mut digest = [0u8; SHA1_DIGEST_LEN];
+        // `Sha1_Final` also reinitializes the state.
+        unsafe { Sha1_Final(&mut self.sha, digest.as_mut_ptr()) };
+        digest
+    }
+
+    /// Discards the hashed data, keeping the selected implementation.
+    pub fn reset(&mut self) {
+        unsafe { Sha1_InitState(&mut self.sha) };
+    }
+}
+
+impl Default for Sha1 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::io::Write for Sha256 {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::io::Write for Sha1 {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Implements the `digest` traits, which give the hashers the [`digest::Digest`] API.
+#[cfg(feature = "digest")]
+macro_rules! impl_digest {
+    ($hasher:ty, $output_size:ty) => {
+        impl digest::HashMarker for $hasher {}
+
+        impl digest::OutputSizeUser for $hasher {
+            type OutputSize = $output_size;
+        }
+
+        impl digest::Update for $hasher {
+            fn update(&mut self, data: &[u8]) {
+                <$hasher>::update(self, data);
+            }
+        }
+
+        impl digest::FixedOutput for $hasher {
+            fn finalize_into(self, out: &mut digest::Output<Self>) {
+                out.copy_from_slice(&<$hasher>::finalize(self));
+            }
+        }
+
+        impl digest::Reset for $hasher {
+            fn reset(&mut self) {
+                <$hasher>::reset(self);
+            }
+        }
+
+        impl digest::FixedOutputReset for $hasher {
+            fn finalize_into_reset(&mut self, out: &mut digest::Output<Self>) {
+                out.copy_from_slice(&<$hasher>::finalize_reset(self));
+            }
+        }
+    };
+}
+
+#[cfg(feature = "digest")]
+impl_digest!(Sha256, digest::consts::U32);
+#[cfg(feature = "digest")]
+impl_digest!(Sha1, digest::consts::U20);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex<const N: usize>(s: &str) -> [u8; N] {
+        let mut out = [0u8; N];
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).unwrap();
+        }
+        out
+    }
+
+    const ABC: &[u8] = b"abc";
+    const TWO_BLOCKS: &[u8] = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+
+    // FIPS 180-2 examples, and the empty message.
+    const SHA256_VECTORS: [(&[u8], &str); 3] = [
+        (b"", "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"),
+        (ABC, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+        (TWO_BLOCKS, "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"),
+    ];
+    const SHA1_VECTORS: [(&[u8], &str); 3] = [
+        (b"", "da39a3ee5e6b4b0d3255bfef95601890afd80709"),
+        (ABC, "a9993e364706816aba3e25717850c26c9cd0d89d"),
+        (TWO_BLOCKS, "84983e441c3bd26ebaae4aa1f95129e5e54670f1"),
+    ];
+
+    fn implementations() -> Vec<ShaImplementation> {
+        let mut implementations = vec![ShaImplementation::Auto, ShaImplementation::Software];
+        if sha256_hardware_accelerated() && sha1_hardware_accelerated() {
+            implementations.push(ShaImplementation::Hardware);
+        }
+        implementations
+    }
+
+    #[test]
+    fn test_sha256_known_answers() {
+        for implementation in implementations() {
+            let mut hasher = Sha256::with_implementation(implementation).unwrap();
+            for (message, digest) in SHA256_VECTORS {
+                hasher.update(message);
+                assert_eq!(hasher.finalize_reset(), hex::<32>(digest), "{implementation:?}");
+            }
+        }
+
+        // FIPS 180-2, one million times "a", fed in odd-sized parts.
+        let mut hasher = Sha256::new();
+        let message = vec![b'a'; 1_000_000];
+        for chunk in message.chunks(999) {
+            hasher.update(chunk);
+        }
+        assert_eq!(
+            hasher.finalize(),
+            hex::<32>("cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0")
+        );
+    }
+
+    #[test]
+    fn test_sha1_known_answers() {
+        for implementation in implementations() {
+            let mut hasher = Sha1::with_implementation(implementation).unwrap();
+            for (message, digest) in SHA1_VECTORS {
+                hasher.update(message);
+                assert_eq!(hasher.finalize_reset(), hex::<20>(digest), "{implementation:?}");
+            }
+        }
+
+        let mut hasher = Sha1::new();
+        let message = vec![b'a'; 1_000_000];
+        for chunk in message.chunks(999) {
+            hasher.update(chunk);
+        }
+        assert_eq!(hasher.finalize(), hex::<20>("34aa973cd4c4daa4f61eeb2bdbad27316534016f"));
+    }
+
+    #[test]
+    fn test_sha_reset() {
+        let mut hasher = Sha256::with_implementation(ShaImplementation::Software).unwrap();
+        hasher.update(b"discarded");
+        hasher.reset();
+        hasher.update(ABC);
+        assert_eq!(hasher.finalize(), Sha256::digest(ABC));
+
+        let mut hasher = Sha1::new();
+        hasher.update(b"discarded");
+        hasher.reset();
+        hasher.update(ABC);
+        assert_eq!(hasher.finalize(), Sha1::digest(ABC));
+    }
+
+    #[cfg(feature = "digest")]
+    #[test]
+    fn test_sha_digest_traits() {
+        use digest::Digest;
+
+        let (message, expected) = SHA256_VECTORS[1];
+        assert_eq!(<Sha256 as Digest>::digest(message)[..], hex::<32>(expected));
+
+        let (message, expected) = SHA1_VECTORS[2];
+        let mut hasher = <Sha1 as Digest>::new();
+        Digest::update(&mut hasher, message);
+        assert_eq!(hasher.finalize_reset()[..], hex::<20>(expected));
+    }
+}
@@ -0,0 +1,421 @@
+use crate::*;
+use core::any::Any;
+use core::ffi::c_void;
+use core::slice;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::panic::{self, AssertUnwindSafe};
+
+/// Payload of a panic caught in a callback, resumed once the SDK call returned.
+pub(crate) type Panic = Box<dyn Any + Send>;
+
+/// Calls `f`, catching a panic so it doesn't unwind into the SDK.
+///
+/// The panic is stored in `panic` and `None` is returned. Once a panic was caught,
+/// `f` isn't called anymore.
+pub(crate) fn catch_panic<T>(panic: &mut Option<Panic>, f: impl FnOnce() -> T) -> Option<T> {
+    if panic.is_some() {
+        return None;
+    }
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(value) => Some(value),
+        Err(payload) => {
+            *panic = Some(payload);
+            None
+        }
+    }
+}
+
+/// Reads into `buf`, retrying on [`io::ErrorKind::Interrupted`].
+fn read_retrying<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    loop {
+        match reader.read(buf) {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            res => return res,
+        }
+    }
+}
+
+/// Adapts a Rust [`Read`] into an LZMA-SDK `ISeqInStream`.
+///
+/// The vtable must be the first field, so a pointer to it can be cast back to the container.
+/// Errors returned by the reader are stored and reported to the SDK as `SZ_ERROR_READ`,
+/// and so are panics, which are resumed by [`SeqInStream::take_error`].
+#[repr(C)]
+pub(crate) struct SeqInStream<'a, R: Read> {
+    vt: ISeqInStream,
+    reader: &'a mut R,
+    error: Option<io::Error>,
+    panic: Option<Panic>,
+}
+
+impl<'a, R: Read> SeqInStream<'a, R> {
+    pub(crate) fn new(reader: &'a mut R) -> Self {
+        Self {
+            vt: ISeqInStream {
+                Read: Some(seq_in_stream_read::<R>),
+            },
+            reader,
+            error: None,
+            panic: None,
+        }
+    }
+
+    /// Returns the pointer to pass to the SDK.
+    pub(crate) fn as_ptr(&mut self) -> ISeqInStreamPtr {
+        self as *mut Self as ISeqInStreamPtr
+    }
+
+    /// Takes the error returned by the reader, if any.
+    ///
+    /// Resumes unwinding if the reader panicked.
+    pub(crate) fn take_error(&mut self) -> Option<io::Error> {
+        if let Some(payload) = self.panic.take() {
+            panic::resume_unwind(payload);
+        }
+        self.error.take()
+    }
+}
+
+unsafe extern "C" fn seq_in_stream_read<R: Read>(p: ISeqInStreamPtr, buf: *mut c_void, size: *mut usize) -> SRes {
+    let stream = &mut *(p as *mut SeqInStream<R>);
+    if *size == 0 {
+        return SZ_OK as SRes;
+    }
+
+    let buf = slice::from_raw_parts_mut(buf as *mut u8, *size);
+    match catch_panic(&mut stream.panic, || read_retrying(stream.reader, buf)) {
+        Some(Ok(read)) => {
+            *size = read;
+            SZ_OK as SRes
+        }
+        Some(Err(e)) => {
+            stream.error = Some(e);
+            *size = 0;
+            SZ_ERROR_READ as SRes
+        }
+        None => {
+            *size = 0;
+            SZ_ERROR_READ as SRes
+        }
+    }
+}
+
+/// Adapts a Rust [`Write`] into an LZMA-SDK `ISeqOutStream`.
+///
+/// Errors returned by the writer are stored, and reported to the SDK as a short write,
+/// which the SDK turns into `SZ_ERROR_WRITE`. The same goes for panics, which are
+/// resumed by [`SeqOutStream::take_error`].
+#[repr(C)]
+pub(crate) struct SeqOutStream<'a, W: Write> {
+    vt: ISeqOutStream,
+    writer: &'a mut W,
+    error: Option<io::Error>,
+    panic: Option<Panic>,
+}
+
+impl<'a, W: Write> SeqOutStream<'a, W> {
+    pub(crate) fn new(writer: &'a mut W) -> Self {
+        Self {
+            vt: ISeqOutStream {
+                Write: Some(seq_out_stream_write::<W>),
+            },
+            writer,
+            error: None,
+            panic: None,
+        }
+    }
+
+    /// Returns the pointer to pass to the SDK.
+    pub(crate) fn as_ptr(&mut self) -> ISeqOutStreamPtr {
+        self as *mut Self as ISeqOutStreamPtr
+    }
+
+    /// Takes the error returned by the writer, if any.
+    ///
+    /// Resumes unwinding if the writer panicked.
+    pub(crate) fn take_error(&mut self) -> Option<io::Error> {
+        if let Some(payload) = self.panic.take() {
+            panic::resume_unwind(payload);
+        }
+        self.error.take()
+    }
+}
+
+unsafe extern "C" fn seq_out_stream_write<W: Write>(p: ISeqOutStreamPtr, buf: *const c_void, size: usize) -> usize {
+    let stream = &mut *(p as *mut SeqOutStream<W>);
+    if size == 0 {
+        return 0;
+    }
+
+    let buf = slice::from_raw_parts(buf as *const u8, size);
+    match catch_panic(&mut stream.panic, || stream.writer.write_all(buf)) {
+        Some(Ok(())) => size,
+        Some(Err(e)) => {
+            stream.error = Some(e);
+            0
+        }
+        None => 0,
+    }
+}
+
+/// Default size of the read-ahead buffer used by [`LookInStream`].
+pub const LOOK_BUFFER_SIZE: usize = 1 << 16;
+
+/// Adapts a Rust [`Read`] + [`Seek`] into an LZMA-SDK `ISeekInStream`.
+///
+/// The vtable is the first field, so the pointer returned by [`SeekInStream::as_ptr`]
+/// can be cast back to the container by the callbacks. Errors returned by the reader
+/// are stored and reported to the SDK as `SZ_ERROR_READ`; retrieve them with
+/// [`SeekInStream::take_error`] after the SDK call returns. Panics are caught the same
+/// way, and resumed by [`SeekInStream::take_error`].
+#[repr(C)]
+pub struct SeekInStream<R: Read + Seek> {
+    vt: ISeekInStream,
+    reader: R,
+    error: Option<io::Error>,
+    panic: Option<Panic>,
+}
+
+impl<R: Read + Seek> SeekInStream<R> {
+    /// Wraps `reader`, which is used from its current position.
+    pub fn new(reader: R) -> Self {
+        Self {
+            vt: ISeekInStream {
+                Read: Some(seek_in_stream_read::<R>),
+                Seek: Some(seek_in_stream_seek::<R>),
+            },
+            reader,
+            error: None,
+            panic: None,
+        }
+    }
+
+    /// Returns the pointer to pass to the SDK.
+    ///
+    /// The pointer is valid as long as `self` is neither moved nor dropped.
+    pub fn as_ptr(&mut self) -> ISeekInStreamPtr {
+        self as *mut Self as ISeekInStreamPtr
+    }
+
+    /// Takes the error returned by the reader, if any.
+    ///
+    /// Resumes unwinding if the reader panicked.
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        if let Some(payload) = self.panic.take() {
+            panic::resume_unwind(payload);
+        }
+        self.error.take()
+    }
+
+    /// Returns a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Returns a mutable reference to the inner reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Consumes the stream, returning the inner reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+unsafe extern "C" fn seek_in_stream_read<R: Read + Seek>(p: ISeekInStreamPtr, buf: *mut c_void, size: *mut usize) -> SRes {
+    let stream = &mut *(p as *mut SeekInStream<R>);
+    if *size == 0 {
+        return SZ_OK as SRes;
+    }
+
+    let buf = slice::from_raw_parts_mut(buf as *mut u8, *size);
+    match catch_panic(&mut stream.panic, || read_retrying(&mut stream.reader, buf)) {
+        Some(Ok(read)) => {
+            *size = read;
+            SZ_OK as SRes
+        }
+        Some(Err(e)) => {
+            stream.error = Some(e);
+            *size = 0;
+            SZ_ERROR_READ as SRes
+        }
+        None => {
+            *size = 0;
+            SZ_ERROR_READ as SRes
+        }
+    }
+}
+
+unsafe extern "C" fn seek_in_stream_seek<R: Read + Seek>(p: ISeekInStreamPtr, pos: *mut Int64, origin: ESzSeek) -> SRes {
+    let stream = &mut *(p as *mut SeekInStream<R>);
+    let target = match origin {
+        ESzSeek::SZ_SEEK_SET => match u64::try_from(*pos) {
+            Ok(offset) => SeekFrom::Start(offset),
+            Err(_) => return SZ_ERROR_PARAM as SRes,
+        },
+        ESzSeek::SZ_SEEK_CUR => SeekFrom::Current(*pos),
+        ESzSeek::SZ_SEEK_END => SeekFrom::End(*pos),
+    };
+
+    match catch_panic(&mut stream.panic, || stream.reader.seek(target)) {
+        Some(Ok(new_pos)) => {
+            *pos = new_pos as Int64;
+            SZ_OK as SRes
+        }
+        Some(Err(e)) => {
+            stream.error = Some(e);
+            SZ_ERROR_READ as SRes
+        }
+        None => SZ_ERROR_READ as SRes,
+    }
+}
+
+/// Buffered LZMA-SDK `ILookInStream` (`CLookToRead2`) over a Rust [`Read`] + [`Seek`].
+///
+/// This is the random-access stream type taken by the archive readers (e.g. `SzArEx_Open`).
+/// The read-ahead buffer is heap allocated and the pointer to the inner [`SeekInStream`]
+/// is refreshed by [`LookInStream::as_ptr`], so the stream may be moved between SDK calls.
+pub struct LookInStream<R: Read + Seek> {
+    look: CLookToRead2,
+    seek: SeekInStream<R>,
+    buf: Box<[u8]>,
+}
+
+impl<R: Read + Seek> LookInStream<R> {
+    /// Wraps `reader` with a read-ahead buffer of [`LOOK_BUFFER_SIZE`] bytes.
+    pub fn new(reader: R) -> Self {
+        Self::with_capacity(LOOK_BUFFER_SIZE, reader)
+    }
+
+    /// Wraps `reader` with a read-ahead buffer of `capacity` bytes (at least 1).
+    pub fn with_capacity(capacity: usize, reader: R) -> Self {
+        let mut stream = Self {
+            look: CLookToRead2::default(),
+            seek: SeekInStream::new(reader),
+            buf: vec![0u8; capacity.max(1)].into_boxed_slice(),
+        };
+
+        unsafe { LookToRead2_CreateVTable(&mut stream.look, 0) };
+        stream.look.buf = stream.buf.as_mut_ptr();
+        stream.look.bufSize = stream.buf.len();
+        // `LookToRead2_INIT`
+        stream.look.pos = 0;
+        stream.look.size = 0;
+        stream
+    }
+
+    /// Returns the pointer to pass to the SDK.
+    ///
+    /// The pointer is valid as long as `self` is neither moved nor dropped.
+    pub fn as_ptr(&mut self) -> ILookInStreamPtr {
+        self.look.realStream = self.seek.as_ptr();
+        &mut self.look as *mut CLookToRead2 as ILookInStreamPtr
+    }
+
+    /// Takes the error returned by the reader, if any.
+    ///
+    /// Resumes unwinding if the reader panicked.
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.seek.take_error()
+    }
+
+    /// Returns a reference to the inner reader.
+    ///
+    /// Its position is ahead of the SDK's logical position by the amount of buffered data.
+    pub fn get_ref(&self) -> &R {
+        self.seek.get_ref()
+    }
+
+    /// Consumes the stream, returning the inner reader.
+    pub fn into_inner(self) -> R {
+        self.seek.into_inner()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::ConnectionReset, "reader failed"))
+        }
+    }
+
+    impl Seek for FailingReader {
+        fn seek(&mut self, _pos: SeekFrom) -> io::Result<u64> {
+            Ok(0)
+        }
+    }
+
+    struct PanickingReader;
+
+    impl Read for PanickingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            panic!("reader panicked");
+        }
+    }
+
+    impl Seek for PanickingReader {
+        fn seek(&mut self, _pos: SeekFrom) -> io::Result<u64> {
+            Ok(0)
+        }
+    }
+
+    #[test]
+    fn test_look_in_stream_random_access() {
+        let data: Vec<u8> = (0..=255).collect();
+        // A small buffer, so reads span several refills.
+        let mut stream = LookInStream::with_capacity(16, Cursor::new(data));
+
+        let mut buf = [0u8; 40];
+        unsafe {
+            check(LookInStream_SeekTo(stream.as_ptr(), 100)).unwrap();
+            check(LookInStream_Read(stream.as_ptr(), buf.as_mut_ptr() as *mut c_void, buf.len())).unwrap();
+        }
+        assert!(buf.iter().copied().eq(100..140));
+
+        // Moving the stream between SDK calls is fine.
+        let mut stream = Box::new(stream);
+        unsafe {
+            check(LookInStream_SeekTo(stream.as_ptr(), 10)).unwrap();
+            check(LookInStream_Read(stream.as_ptr(), buf.as_mut_ptr() as *mut c_void, 4)).unwrap();
+        }
+        assert_eq!(buf[..4], [10, 11, 12, 13]);
+
+        // Reading past the end fails without an io error.
+        let res = unsafe {
+            LookInStream_SeekTo(stream.as_ptr(), 250);
+            LookInStream_Read(stream.as_ptr(), buf.as_mut_ptr() as *mut c_void, 10)
+        };
+        assert_eq!(res, SZ_ERROR_INPUT_EOF as SRes);
+        assert!(stream.take_error().is_none());
+    }
+
+    #[test]
+    fn test_look_in_stream_reader_error() {
+        let mut stream = LookInStream::new(FailingReader);
+        let mut buf = [0u8; 4];
+        let res = unsafe { LookInStream_Read(stream.as_ptr(), buf.as_mut_ptr() as *mut c_void, buf.len()) };
+        assert_eq!(res, SZ_ERROR_READ as SRes);
+        assert_eq!(stream.take_error().unwrap().kind(), io::ErrorKind::ConnectionReset);
+        assert!(stream.take_error().is_none());
+    }
+
+    #[test]
+    fn test_look_in_stream_reader_panic() {
+        let mut stream = LookInStream::new(PanickingReader);
+        let mut buf = [0u8; 4];
+        let res = unsafe { LookInStream_Read(stream.as_ptr(), buf.as_mut_ptr() as *mut c_void, buf.len()) };
+        assert_eq!(res, SZ_ERROR_READ as SRes);
+
+        // The panic is resumed once the SDK call returned.
+        let payload = panic::catch_unwind(AssertUnwindSafe(|| stream.take_error())).unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"reader panicked"));
+        assert!(stream.take_error().is_none());
+    }
+}
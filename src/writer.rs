@@ -0,0 +1,376 @@
+use crate::*;
+use core::mem;
+use std::io::{self, Write};
+use std::io::Read;
+use std::panic;
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::thread::{self, JoinHandle};
+
+/// Amount of uncompressed data buffered before it is handed to the encoder.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Number of chunks that may be queued for the encoder before `write` blocks.
+const MAX_QUEUED_CHUNKS: usize = 4;
+
+/// Streaming LZMA compressor implementing [`Write`].
+///
+/// Output uses the classic `.lzma` layout: the 5-byte properties header, a 64-bit
+/// unknown size (`u64::MAX`) and an LZMA stream terminated with an end marker,
+/// which can be read back with [`LzmaReader::new`].
+///
+/// `LzmaEnc_Encode` pulls its input and can't be suspended between `write` calls, so only
+/// the encoder runs on a worker thread; the inner writer stays on the calling thread and
+/// receives the compressed output from `write`, `flush` and `finish`. At most a few chunks
+/// are queued for the encoder, so memory use doesn't grow with the input size. The `st`
+/// feature only turns off the LZMA SDK's own threads, the worker is still needed then.
+///
+/// Call [`LzmaWriter::finish`] to complete the stream and get the inner writer back.
+/// Dropping the writer completes the stream too, ignoring errors.
+pub struct LzmaWriter<W: Write> {
+    // Both are taken once the stream is finished.
+    inner: Option<W>,
+    encoder: Option<Encoder>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> LzmaWriter<W> {
+    /// Creates a writer producing a classic `.lzma` stream with the given encoder properties.
+    pub fn new(inner: W, props: &CLzmaEncProps) -> io::Result<Self> {
+        Self::create(inner, props, true)
+    }
+
+    /// Creates a writer producing only the raw LZMA stream (with end marker), without
+    /// the properties header and size. Use [`LzmaEncoder::write_properties`] with the
+    /// same properties to obtain the header.
+    pub fn without_header(inner: W, props: &CLzmaEncProps) -> io::Result<Self> {
+        Self::create(inner, props, false)
+    }
+
+    fn create(mut inner: W, props: &CLzmaEncProps, write_header: bool) -> io::Result<Self> {
+        // The stream length isn't known up front, so the decoder relies on the end marker.
+        let mut props = *props;
+        props.writeEndMark = 1;
+
+        let encoder = LzmaEncoder::with_props(&props)?;
+        if write_header {
+            let header = encoder.write_properties()?;
+            inner.write_all(&header)?;
+            inner.write_all(&u64::MAX.to_le_bytes())?;
+        }
+
+        Ok(Self {
+            inner: Some(inner),
+            encoder: Some(Encoder::new(encoder)),
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+        })
+    }
+
+    /// Finishes the LZMA stream and returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.finish_stream()?;
+        self.inner.take().ok_or_else(stream_finished)
+    }
+
+    fn finish_stream(&mut self) -> io::Result<()> {
+        let (Some(encoder), Some(inner)) = (self.encoder.take(), self.inner.as_mut()) else {
+            return Err(stream_finished());
+        };
+        encoder.finish(mem::take(&mut self.buffer), inner)?;
+        inner.flush()
+    }
+
+    fn send_buffer(&mut self) -> io::Result<()> {
+        let (Some(encoder), Some(inner)) = (self.encoder.as_mut(), self.inner.as_mut()) else {
+            return Err(stream_finished());
+        };
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let chunk = mem::replace(&mut self.buffer, Vec::with_capacity(CHUNK_SIZE));
+        let res = encoder.write_chunk(chunk, inner);
+        if res.is_err() {
+            // The stream is broken, later calls fail instead of writing more output.
+            self.encoder = None;
+        }
+        res
+    }
+}
+
+fn stream_finished() -> io::Error {
+    io::Error::other("LZMA stream already finished or failed")
+}
+
+impl<W: Write> Write for LzmaWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let count = buf.len().min(CHUNK_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..count]);
+        if self.buffer.len() == CHUNK_SIZE {
+            self.send_buffer()?;
+        }
+        Ok(count)
+    }
+
+    /// Hands buffered data to the encoder and writes the output produced so far.
+    /// The compressed output is only complete once [`LzmaWriter::finish`] is called.
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_buffer()?;
+        match self.inner.as_mut() {
+            Some(inner) => inner.flush(),
+            None => Err(stream_finished()),
+        }
+    }
+}
+
+impl<W: Write> Drop for LzmaWriter<W> {
+    fn drop(&mut self) {
+        // A worker panic is re-raised by `finish_stream`, which must not happen while unwinding.
+        if self.encoder.is_some() && !std::thread::panicking() {
+            let _ = self.finish_stream();
+        }
+    }
+}
+
+/// Runs `LzmaEnc_Encode` on a worker thread, exchanging input and output chunks over channels.
+struct Encoder {
+    input: SyncSender<Vec<u8>>,
+    output: Receiver<Vec<u8>>,
+    worker: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl Encoder {
+    fn new(mut encoder: LzmaEncoder) -> Self {
+        let (input, input_receiver) = mpsc::sync_channel(MAX_QUEUED_CHUNKS);
+        // Unbounded, so the encoder never waits for `write` while `write` waits for the encoder.
+        let (output_sender, output) = mpsc::channel();
+        let worker = thread::spawn(move || {
+            let mut input = ChannelReader {
+                receiver: input_receiver,
+                chunk: Vec::new(),
+                pos: 0,
+            };
+            let mut output = ChannelWriter { sender: output_sender };
+            encoder.encode_stream(&mut input, &mut output)
+        });
+
+        Self {
+            input,
+            output,
+            worker: Some(worker),
+        }
+    }
+
+    /// Queues `chunk` for the encoder, then writes the output produced so far to `inner`.
+    fn write_chunk<W: Write>(&mut self, chunk: Vec<u8>, inner: &mut W) -> io::Result<()> {
+        if self.input.send(chunk).is_err() {
+            // The encoder stopped before the end of the input, report why.
+            return Err(match Self::join(&mut self.worker) {
+                Err(e) => e,
+                Ok(()) => io::Error::other("LZMA encoder stopped unexpectedly"),
+            });
+        }
+
+        while let Ok(output) = self.output.try_recv() {
+            inner.write_all(&output)?;
+        }
+        Ok(())
+    }
+
+    /// Ends the input with `chunk` and writes the rest of the output to `inner`.
+    fn finish<W: Write>(mut self, chunk: Vec<u8>, inner: &mut W) -> io::Result<()> {
+        if !chunk.is_empty() {
+            self.write_chunk(chunk, inner)?;
+        }
+
+        let Self {
+            input,
+            output,
+            mut worker,
+        } = self;
+        // Closing the input channel signals the end of the input to the encoder.
+        drop(input);
+        // The output channel closes once the encoder returns. If `inner` fails, dropping
+        // the receiver makes the encoder fail on its next write, so it stops early.
+        let written = output.iter().try_for_each(|output| inner.write_all(&output));
+        drop(output);
+        written.and(Self::join(&mut worker))
+    }
+
+    /// Waits for the worker, re-raising its panic on this thread.
+    fn join(worker: &mut Option<JoinHandle<io::Result<()>>>) -> io::Result<()> {
+        match worker.take() {
+            Some(worker) => worker.join().unwrap_or_else(|payload| panic::resume_unwind(payload)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Feeds chunks received from [`LzmaWriter`] to the encoder thread.
+struct ChannelReader {
+    receiver: Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.chunk.len() {
+            match self.receiver.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                // All senders dropped, end of input.
+                Err(_) => return Ok(0),
+            }
+        }
+
+        let count = buf.len().min(self.chunk.len() - self.pos);
+        buf[..count].copy_from_slice(&self.chunk[self.pos..self.pos + count]);
+        self.pos += count;
+        Ok(count)
+    }
+}
+
+/// Sends the compressed output of the encoder thread back to [`LzmaWriter`].
+struct ChannelWriter {
+    sender: Sender<Vec<u8>>,
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.sender
+            .send(buf.to_vec())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "LzmaWriter was dropped"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Read};
+
+    fn sample_data() -> Vec<u8> {
+        (0..300_000u32).map(|x| (x % 239) as u8 ^ (x / 4096) as u8).collect()
+    }
+
+    #[test]
+    fn test_writer_round_trip() {
+        let input = sample_data();
+        let mut props = CLzmaEncProps::default();
+        unsafe { LzmaEncProps_Init(&mut props) };
+
+        let mut writer = LzmaWriter::new(Vec::new(), &props).unwrap();
+        for chunk in input.chunks(1000) {
+            writer.write_all(chunk).unwrap();
+        }
+        let compressed = writer.finish().unwrap();
+        assert!(compressed.len() < input.len());
+
+        let mut output = Vec::new();
+        LzmaReader::new(Cursor::new(compressed)).unwrap().read_to_end(&mut output).unwrap();
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn test_writer_borrowed_inner() {
+        let input = sample_data();
+        let mut props = CLzmaEncProps::default();
+        unsafe { LzmaEncProps_Init(&mut props) };
+
+        let mut compressed = Vec::new();
+        let mut writer = LzmaWriter::new(&mut compressed, &props).unwrap();
+        writer.write_all(&input).unwrap();
+        writer.flush().unwrap();
+        writer.finish().unwrap();
+
+        let mut output = Vec::new();
+        LzmaReader::new(Cursor::new(compressed)).unwrap().read_to_end(&mut output).unwrap();
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn test_writer_memory_bounded() {
+        // Incompressible data, so the encoder keeps producing output while the input arrives.
+        let mut state = 0x2545_f491u32;
+        let input: Vec<u8> = (0..4 * 1024 * 1024)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 24) as u8
+            })
+            .collect();
+        let mut props = CLzmaEncProps::default();
+        unsafe { LzmaEncProps_Init(&mut props) };
+        props.level = 1;
+
+        let mut writer = LzmaWriter::without_header(Vec::new(), &props).unwrap();
+        let mut peak_capacity = 0;
+        for chunk in input.chunks(10_000) {
+            writer.write_all(chunk).unwrap();
+            peak_capacity = peak_capacity.max(writer.buffer.capacity());
+        }
+        assert!(peak_capacity <= CHUNK_SIZE);
+        // Output was written while the input arrived, rather than only on `finish`.
+        assert!(!writer.inner.as_ref().unwrap().is_empty());
+        let compressed = writer.finish().unwrap();
+
+        let mut output = Vec::new();
+        let header = LzmaEncoder::with_props(&props).unwrap().write_properties().unwrap();
+        LzmaReader::with_props(Cursor::new(compressed), &header, None)
+            .unwrap()
+            .read_to_end(&mut output)
+            .unwrap();
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn test_writer_inner_error() {
+        struct FailingWriter;
+
+        impl Write for FailingWriter {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk full"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut props = CLzmaEncProps::default();
+        unsafe { LzmaEncProps_Init(&mut props) };
+        let mut writer = LzmaWriter::without_header(FailingWriter, &props).unwrap();
+
+        // Depending on how fast the encoder is, the error comes from `write_all` or `finish`.
+        let err = match writer.write_all(&sample_data()) {
+            Err(e) => e,
+            Ok(()) => writer.finish().err().unwrap(),
+        };
+        assert_eq!(err.to_string(), "disk full");
+    }
+
+    #[test]
+    fn test_encode_stream_round_trip() {
+        let input = sample_data();
+        let mut props = CLzmaEncProps::default();
+        unsafe { LzmaEncProps_Init(&mut props) };
+        props.writeEndMark = 1;
+
+        let mut encoder = LzmaEncoder::with_props(&props).unwrap();
+        let header = encoder.write_properties().unwrap();
+        let mut compressed = Vec::new();
+        encoder.encode_stream(&mut Cursor::new(&input), &mut compressed).unwrap();
+
+        let mut output = Vec::new();
+        LzmaReader::with_props(Cursor::new(compressed), &header, None)
+            .unwrap()
+            .read_to_end(&mut output)
+            .unwrap();
+        assert_eq!(output, input);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::crc::init_tables;
+use crate::*;
+use alloc::boxed::Box;
+
+/// Result of a single [`XzDecoder::decode`] call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XzDecodeProgress {
+    /// Number of bytes consumed from the input buffer.
+    pub consumed: usize,
+    /// Number of bytes written to the output buffer.
+    pub produced: usize,
+    /// Decoder status after this call.
+    pub status: ECoderStatus,
+}
+
+/// Safe incremental `.xz` decoder over `CXzUnpacker`.
+///
+/// Handles multiple concatenated streams, stream padding, and verifies the
+/// CRC32, CRC64 and SHA-256 checks of each block.
+///
+/// ```ignore
+/// Stream [Padding] Stream [Padding] ...
+/// ```
+pub struct XzDecoder {
+    // Boxed, since the unpacker keeps a pointer to the allocator.
+    state: Box<CXzUnpacker>,
+    alloc: Box<Allocator>,
+}
+
+// The decoder state is exclusively owned and only ever accessed through `&mut self`.
+unsafe impl Send for XzDecoder {}
+
+impl Default for XzDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl XzDecoder {
+    /// Creates a new decoder.
+    ///
+    /// Memory for the block decoders is allocated lazily, based on the block headers.
+    pub fn new() -> Self {
+        init_tables();
+
+        let mut decoder = Self {
+            state: Box::default(),
+            alloc: Box::default(),
+        };
+        unsafe { XzUnpacker_Construct(decoder.state.as_mut(), decoder.alloc.as_ref().as_ref()) };
+        decoder
+    }
+
+    /// Resets the decoder so a new `.xz` file can be decoded.
+    pub fn reset(&mut self) {
+        unsafe { XzUnpacker_Init(self.state.as_mut()) };
+    }
+
+    /// Decodes as much of `input` into `output` as possible.
+    ///
+    /// Call repeatedly, advancing `input` by [`XzDecodeProgress::consumed`], until all input
+    /// was consumed and no more output is produced. Then use [`XzDecoder::is_finished`] to
+    /// check whether the input ended at a valid position.
+    ///
+    /// # Arguments
+    /// * `input` - Compressed data to consume.
+    /// * `output` - Buffer receiving decompressed data.
+    /// * `input_finished` - True if `input` contains the remainder of the file.
+    ///
+    /// # Returns
+    /// * `Result<XzDecodeProgress, LzmaError>` - The progress, or an error such as
+    ///   [`LzmaError::Crc`] for a check mismatch or [`LzmaError::NoArchive`] for data that is not `.xz`.
+    pub fn decode(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        input_finished: bool,
+    ) -> Result<XzDecodeProgress, LzmaError> {
+        let mut consumed = input.len() as SizeT;
+        let mut produced = output.len() as SizeT;
+        let mut status = ECoderStatus::CODER_STATUS_NOT_SPECIFIED;
+
+        check(unsafe {
+            XzUnpacker_Code(
+                self.state.as_mut(),
+                output.as_mut_ptr(),
+                &mut produced,
+                input.as_ptr(),
+                &mut consumed,
+                input_finished as BoolInt,
+                ECoderFinishMode::CODER_FINISH_ANY,
+                &mut status,
+            )
+        })?;
+
+        Ok(XzDecodeProgress { consumed, produced, status })
+    }
+
+    /// Returns true if the data decoded so far ends with a complete stream (and valid padding),
+    /// i.e. the input may legitimately end here.
+    pub fn is_finished(&self) -> bool {
+        unsafe { XzUnpacker_IsStreamWasFinished(self.state.as_ref()) != 0 }
+    }
+
+    /// Returns the number of streams decoded completely so far.
+    pub fn finished_streams(&self) -> u64 {
+        self.state.numFinishedStreams
+    }
+}
+
+impl Drop for XzDecoder {
+    fn drop(&mut self) {
+        unsafe { XzUnpacker_Free(self.state.as_mut()) };
+    }
+}
+
+/// Decompresses a complete `.xz` file held in memory.
+///
+/// # Returns
+/// * `Result<Vec<u8>, LzmaError>` - The decompressed data of all streams, or
+///   [`LzmaError::InputEof`] if the file is truncated.
+pub fn decompress_xz(data: &[u8]) -> Result<alloc::vec::Vec<u8>, LzmaError> {
+    let mut decoder = XzDecoder::new();
+    let mut output = alloc::vec::Vec::new();
+    let mut chunk = alloc::vec![0u8; 64 * 1024];
+    let mut remaining = data;
+    loop {
+        let progress = decoder.decode(remaining, &mut chunk, true)?;
+        remaining = &remaining[progress.consumed..];
+        output.extend_from_slice(&chunk[..progress.produced]);
+        if progress.consumed == 0 && progress.produced == 0 {
+            break;
+        }
+    }
+
+    if !remaining.is_empty() || !decoder.is_finished() {
+        return Err(LzmaError::InputEof);
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELLO: &[u8] = b"Hello from xz!\nHello from xz!\nHello from xz!\n";
+
+    #[test]
+    fn test_xz_decoder_check_types() {
+        let fixtures: [&[u8]; 4] = [
+            include_bytes!("../tests/fixtures/hello_none.xz"),
+            include_bytes!("../tests/fixtures/hello_crc32.xz"),
+            include_bytes!("../tests/fixtures/hello_crc64.xz"),
+            include_bytes!("../tests/fixtures/hello_sha256.xz"),
+        ];
+
+        for fixture in fixtures {
+            assert_eq!(decompress_xz(fixture).unwrap(), HELLO);
+        }
+    }
+
+    #[test]
+    fn test_xz_decoder_concatenated_and_padded() {
+        // Two streams produced by `xz`, separated and followed by null padding.
+        let fixture = include_bytes!("../tests/fixtures/concatenated_padded.xz");
+        let mut decoder = XzDecoder::new();
+        let mut output = [0u8; 64];
+        let mut produced = 0;
+        let mut remaining = &fixture[..];
+        loop {
+            // Feed byte by byte, to exercise stream boundaries.
+            let feed = &remaining[..remaining.len().min(1)];
+            let progress = decoder.decode(feed, &mut output[produced..], remaining.len() <= 1).unwrap();
+            remaining = &remaining[progress.consumed..];
+            produced += progress.produced;
+            if remaining.is_empty() && progress.produced == 0 {
+                break;
+            }
+        }
+
+        assert!(decoder.is_finished());
+        assert_eq!(decoder.finished_streams(), 2);
+        assert_eq!(&output[..produced], b"first stream\nsecond stream\n");
+    }
+
+    #[test]
+    fn test_xz_decoder_rejects_bad_input() {
+        let fixture = include_bytes!("../tests/fixtures/hello_crc64.xz");
+
+        let mut corrupt = fixture.to_vec();
+        // The CRC64 check follows the compressed data, 8 bytes before the index.
+        corrupt[56] ^= 1;
+        assert_eq!(decompress_xz(&corrupt), Err(LzmaError::Crc));
+
+        assert!(decompress_xz(&fixture[..fixture.len() - 4]).is_err());
+        assert_eq!(decompress_xz(b"not an xz file"), Err(LzmaError::NoArchive));
+
+        // Padding must be a multiple of 4 bytes.
+        let mut padded = fixture.to_vec();
+        padded.extend_from_slice(&[0, 0]);
+        assert_eq!(decompress_xz(&padded), Err(LzmaError::InputEof));
+    }
+}
@@ -0,0 +1,352 @@
+use crate::crc::init_tables;
+use crate::progress::{check_progress, ProgressBridge, ProgressFn};
+use crate::streams::{SeqInStream, SeqOutStream};
+use crate::*;
+use core::ops::ControlFlow;
+use core::ptr;
+use std::io::{self, Read, Write};
+
+/// Integrity check stored after each block of an `.xz` stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum XzCheck {
+    None,
+    Crc32,
+    Crc64,
+    Sha256,
+}
+
+impl XzCheck {
+    /// Returns the `XZ_CHECK_*` id used by `CXzProps`.
+    fn to_raw(self) -> u32 {
+        match self {
+            XzCheck::None => XZ_CHECK_NO,
+            XzCheck::Crc32 => XZ_CHECK_CRC32,
+            XzCheck::Crc64 => XZ_CHECK_CRC64,
+            XzCheck::Sha256 => XZ_CHECK_SHA256,
+        }
+    }
+}
+
+/// Filter applied to the data before LZMA2 compression, to make executables
+/// or fixed-width binary data more compressible.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum XzFilter {
+    /// BCJ filter for x86 and x86-64 executables.
+    X86,
+    /// BCJ filter for big endian PowerPC executables.
+    PowerPc,
+    /// BCJ filter for Itanium executables.
+    Ia64,
+    /// BCJ filter for little endian ARM executables.
+    Arm,
+    /// BCJ filter for ARM Thumb executables.
+    ArmThumb,
+    /// BCJ filter for SPARC executables.
+    Sparc,
+    /// BCJ filter for ARM64 executables.
+    Arm64,
+    /// BCJ filter for RISC-V executables.
+    RiscV,
+    /// Delta filter with the given distance in bytes (1-256).
+    Delta(u32),
+}
+
+impl XzFilter {
+    /// Returns the `XZ_ID_*` filter id.
+    fn id(self) -> u32 {
+        match self {
+            XzFilter::X86 => XZ_ID_X86,
+            XzFilter::PowerPc => XZ_ID_PPC,
+            XzFilter::Ia64 => XZ_ID_IA64,
+            XzFilter::Arm => XZ_ID_ARM,
+            XzFilter::ArmThumb => XZ_ID_ARMT,
+            XzFilter::Sparc => XZ_ID_SPARC,
+            XzFilter::Arm64 => XZ_ID_ARM64,
+            XzFilter::RiscV => XZ_ID_RISCV,
+            XzFilter::Delta(_) => XZ_ID_Delta,
+        }
+    }
+}
+
+/// Builder for `CXzProps`.
+///
+/// Each `.xz` block is an independent LZMA2 stream, so blocks can be compressed
+/// in parallel by up to `block_threads` threads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XzEncoderOptions {
+    lzma: LzmaEncoderOptions,
+    check: XzCheck,
+    filter: Option<XzFilter>,
+    block_size: Option<u64>,
+    block_threads: Option<u32>,
+    total_threads: Option<u32>,
+}
+
+impl Default for XzEncoderOptions {
+    fn default() -> Self {
+        Self::preset(5)
+    }
+}
+
+impl XzEncoderOptions {
+    /// Creates options for the given preset level (0 = fastest, 9 = best compression), with a CRC64 check.
+    pub fn preset(level: u32) -> Self {
+        Self::from_lzma(LzmaEncoderOptions::preset(level))
+    }
+
+    /// Creates options from LZMA encoder options, used for each block.
+    pub fn from_lzma(lzma: LzmaEncoderOptions) -> Self {
+        Self {
+            lzma,
+            check: XzCheck::Crc64,
+            filter: None,
+            block_size: None,
+            block_threads: None,
+            total_threads: None,
+        }
+    }
+
+    /// Sets the integrity check stored for each block.
+    pub fn check(mut self, check: XzCheck) -> Self {
+        self.check = check;
+        self
+    }
+
+    /// Sets a filter applied before compression.
+    pub fn filter(mut self, filter: XzFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Sets the uncompressed size of each block.
+    ///
+    /// Use [`LZMA2_BLOCK_SIZE_AUTO`] (default) or [`LZMA2_BLOCK_SIZE_SOLID`] for special behaviour.
+    pub fn block_size(mut self, block_size: u64) -> Self {
+        self.block_size = Some(block_size);
+        self
+    }
+
+    /// Sets the maximum number of blocks encoded in parallel (`numBlockThreads_Max`).
+    pub fn block_threads(mut self, block_threads: u32) -> Self {
+        self.block_threads = Some(block_threads);
+        self
+    }
+
+    /// Sets the total number of threads used, including the threads of each LZMA2 encoder.
+    pub fn total_threads(mut self, total_threads: u32) -> Self {
+        self.total_threads = Some(total_threads);
+        self
+    }
+
+    /// Validates the options and produces encoder properties.
+    ///
+    /// # Returns
+    /// * `Result<CXzProps, LzmaError>` - The properties, or [`LzmaError::Param`] if any value is out of range.
+    ///   The remaining defaults are filled in by `XzEnc_SetProps`.
+    pub fn build(&self) -> Result<CXzProps, LzmaError> {
+        let valid = self.block_threads != Some(0)
+            && self.total_threads != Some(0)
+            && !matches!(self.filter, Some(XzFilter::Delta(distance)) if !(1..=256).contains(&distance));
+        if !valid {
+            return Err(LzmaError::Param);
+        }
+
+        let mut props = CXzProps::default();
+        unsafe { XzProps_Init(&mut props) };
+        props.lzma2Props.lzmaProps = self.lzma.build()?;
+        props.checkId = self.check.to_raw();
+        if let Some(filter) = self.filter {
+            props.filterProps.id = filter.id();
+            if let XzFilter::Delta(distance) = filter {
+                props.filterProps.delta = distance;
+            }
+        }
+        if let Some(block_size) = self.block_size {
+            props.blockSize = block_size;
+        }
+        if let Some(block_threads) = self.block_threads {
+            props.numBlockThreads_Max = block_threads as i32;
+        }
+        if let Some(total_threads) = self.total_threads {
+            props.numTotalThreads = total_threads as i32;
+        }
+
+        Ok(props)
+    }
+}
+
+/// Safe owning handle around an `.xz` encoder (`CXzEncHandle`).
+///
+/// Produces a single `.xz` stream, readable by [`XzDecoder`] and `xz` itself.
+pub struct XzEncoder {
+    handle: CXzEncHandle,
+    // Never read, but boxed and kept alive since the encoder keeps a pointer to it.
+    _alloc: Box<Allocator>,
+    progress: Option<Box<ProgressFn>>,
+}
+
+// The handle is exclusively owned and only ever accessed through `&mut self`.
+unsafe impl Send for XzEncoder {}
+
+impl XzEncoder {
+    /// Creates a new encoder with default properties.
+    ///
+    /// # Returns
+    /// * `Result<Self, LzmaError>` - The encoder, or [`LzmaError::Mem`] if allocation failed.
+    pub fn new() -> Result<Self, LzmaError> {
+        Self::with_options(&XzEncoderOptions::default())
+    }
+
+    /// Creates a new encoder and applies the given properties.
+    pub fn with_props(props: &CXzProps) -> Result<Self, LzmaError> {
+        init_tables();
+
+        let alloc = Box::new(Allocator::default());
+        let handle = unsafe { XzEnc_Create(alloc.as_ref().as_ref(), alloc.as_ref().as_ref()) };
+        if handle.is_null() {
+            return Err(LzmaError::Mem);
+        }
+
+        let mut encoder = Self {
+            handle,
+            _alloc: alloc,
+            progress: None,
+        };
+        encoder.set_props(props)?;
+        Ok(encoder)
+    }
+
+    /// Creates a new encoder from validated [`XzEncoderOptions`].
+    pub fn with_options(options: &XzEncoderOptions) -> Result<Self, LzmaError> {
+        Self::with_props(&options.build()?)
+    }
+
+    /// Applies encoder properties.
+    pub fn set_props(&mut self, props: &CXzProps) -> Result<(), LzmaError> {
+        check(unsafe { XzEnc_SetProps(self.handle, props) })
+    }
+
+    /// Sets the expected size of the input, which lets the encoder reduce memory usage.
+    pub fn set_data_size(&mut self, size: u64) {
+        unsafe { XzEnc_SetDataSize(self.handle, size) };
+    }
+
+    /// Sets a callback receiving `(in_size, out_size)` as compression progresses.
+    /// Returning [`ControlFlow::Break`] cancels the operation with [`LzmaError::Cancelled`].
+    pub fn set_progress<F: FnMut(u64, u64) -> ControlFlow<()> + Send + 'static>(&mut self, progress: F) {
+        self.progress = Some(Box::new(progress));
+    }
+
+    /// Compresses `input` into a newly allocated `.xz` file.
+    pub fn encode_to_vec(&mut self, input: &[u8]) -> io::Result<Vec<u8>> {
+        let mut output = Vec::with_capacity(input.len() / 2 + 128);
+        self.set_data_size(input.len() as u64);
+        let res = self.encode_stream(&mut &input[..], &mut output);
+        // Back to unknown, so the size doesn't leak into later calls of `encode_stream`.
+        self.set_data_size(u64::MAX);
+        res?;
+        Ok(output)
+    }
+
+    /// Compresses everything from `input` into `output` as a single `.xz` stream.
+    ///
+    /// When multithreaded, the streams may be accessed from worker threads, hence the `Send` bounds.
+    pub fn encode_stream<R: Read + Send, W: Write + Send>(&mut self, input: &mut R, output: &mut W) -> io::Result<()> {
+        let mut in_stream = SeqInStream::new(input);
+        let mut out_stream = SeqOutStream::new(output);
+        let mut progress = self.progress.as_deref_mut().map(ProgressBridge::new);
+        let progress_ptr = progress.as_mut().map_or(ptr::null(), |p| p.as_ptr());
+        let res = unsafe { XzEnc_Encode(self.handle, out_stream.as_ptr(), in_stream.as_ptr(), progress_ptr) };
+
+        if let Some(e) = in_stream.take_error().or(out_stream.take_error()) {
+            return Err(e);
+        }
+        check_progress(progress.as_mut(), res)?;
+        Ok(())
+    }
+
+    /// Returns the raw encoder handle, for use with APIs not covered by this wrapper.
+    pub fn as_ptr(&self) -> CXzEncHandle {
+        self.handle
+    }
+}
+
+impl Drop for XzEncoder {
+    fn drop(&mut self) {
+        unsafe { XzEnc_Destroy(self.handle) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::{Command, Stdio};
+
+    fn sample_data(len: u32) -> Vec<u8> {
+        (0..len).map(|x| (x % 251) as u8 ^ (x >> 12) as u8).collect()
+    }
+
+    /// Verifies `data` with `xz -t`, if `xz` is installed.
+    fn verify_with_xz(data: &[u8]) {
+        let child = Command::new("xz")
+            .args(["-t", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let Ok(mut child) = child else {
+            return;
+        };
+
+        child.stdin.take().unwrap().write_all(data).unwrap();
+        assert!(child.wait().unwrap().success(), "xz -t rejected the stream");
+    }
+
+    #[test]
+    fn test_xz_encoder_check_types() {
+        let input = sample_data(100_000);
+        for check in [XzCheck::None, XzCheck::Crc32, XzCheck::Crc64, XzCheck::Sha256] {
+            let mut encoder = XzEncoder::with_options(&XzEncoderOptions::preset(1).check(check)).unwrap();
+            let compressed = encoder.encode_to_vec(&input).unwrap();
+            assert_eq!(decompress_xz(&compressed).unwrap(), input);
+            verify_with_xz(&compressed);
+        }
+    }
+
+    #[test]
+    fn test_xz_encoder_filters() {
+        let input = sample_data(50_000);
+        for filter in [XzFilter::X86, XzFilter::Arm, XzFilter::Delta(4)] {
+            let mut encoder = XzEncoder::with_options(&XzEncoderOptions::preset(1).filter(filter)).unwrap();
+            let compressed = encoder.encode_to_vec(&input).unwrap();
+            assert_eq!(decompress_xz(&compressed).unwrap(), input);
+            verify_with_xz(&compressed);
+        }
+
+        let options = XzEncoderOptions::default().filter(XzFilter::Delta(257));
+        assert_eq!(options.build(), Err(LzmaError::Param));
+    }
+
+    #[test]
+    fn test_xz_encoder_multithreaded_blocks() {
+        let input = sample_data(2 << 20);
+        let options = XzEncoderOptions::preset(1).block_size(1 << 18).block_threads(4);
+        let mut encoder = XzEncoder::with_options(&options).unwrap();
+
+        let mut compressed = Vec::new();
+        encoder.encode_stream(&mut &input[..], &mut compressed).unwrap();
+        verify_with_xz(&compressed);
+
+        let mut output = Vec::new();
+        XzReader::new(&compressed[..]).read_to_end(&mut output).unwrap();
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn test_xz_encoder_empty_input() {
+        let mut encoder = XzEncoder::new().unwrap();
+        let compressed = encoder.encode_to_vec(&[]).unwrap();
+        assert!(decompress_xz(&compressed).unwrap().is_empty());
+        verify_with_xz(&compressed);
+    }
+}
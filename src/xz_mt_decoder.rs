@@ -0,0 +1,285 @@
+use crate::crc::init_tables;
+use crate::progress::{check_progress, ProgressBridge, ProgressFn};
+use crate::streams::{SeqInStream, SeqOutStream};
+use crate::*;
+use core::ops::ControlFlow;
+use core::ptr;
+use std::io::{self, Read, Write};
+
+/// Builder for `CXzDecMtProps`.
+///
+/// Multithreaded decoding needs blocks which store their sizes in the block header,
+/// as written by multithreaded encoders (e.g. [`XzEncoder`] with several block threads,
+/// or `xz -T`). Other files, including single-block files, are decoded on a single thread.
+///
+/// When the crate is built with the `st` feature, only the single-threaded settings apply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct XzMtDecoderOptions {
+    threads: Option<u32>,
+    memory_limit: Option<usize>,
+    in_buf_size_st: Option<usize>,
+    out_step_st: Option<usize>,
+    in_buf_size_mt: Option<usize>,
+}
+
+impl XzMtDecoderOptions {
+    /// Sets the number of decoder threads. Ignored with the `st` feature.
+    pub fn threads(mut self, threads: u32) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Sets the maximum amount of memory used for buffering blocks when multithreaded (`memUseMax`).
+    ///
+    /// Blocks that don't fit the limit are decoded on a single thread. Ignored with the `st` feature.
+    pub fn memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = Some(bytes);
+        self
+    }
+
+    /// Sets the size of the input buffer used for single-threaded decoding (`inBufSize_ST`).
+    pub fn in_buf_size_st(mut self, size: usize) -> Self {
+        self.in_buf_size_st = Some(size);
+        self
+    }
+
+    /// Sets how much data is decoded between writes when single-threaded (`outStep_ST`).
+    pub fn out_step_st(mut self, size: usize) -> Self {
+        self.out_step_st = Some(size);
+        self
+    }
+
+    /// Sets the size of the input buffers used by each thread (`inBufSize_MT`). Ignored with the `st` feature.
+    pub fn in_buf_size_mt(mut self, size: usize) -> Self {
+        self.in_buf_size_mt = Some(size);
+        self
+    }
+
+    /// Validates the options and produces decoder properties.
+    ///
+    /// # Returns
+    /// * `Result<CXzDecMtProps, LzmaError>` - The properties with unset values taken
+    ///   from `XzDecMtProps_Init`, or [`LzmaError::Param`] if any value is zero.
+    pub fn build(&self) -> Result<CXzDecMtProps, LzmaError> {
+        let valid = self.threads != Some(0)
+            && self.memory_limit != Some(0)
+            && self.in_buf_size_st != Some(0)
+            && self.out_step_st != Some(0)
+            && self.in_buf_size_mt != Some(0);
+        if !valid {
+            return Err(LzmaError::Param);
+        }
+
+        let mut props = CXzDecMtProps::default();
+        unsafe { XzDecMtProps_Init(&mut props) };
+        if let Some(size) = self.in_buf_size_st {
+            props.inBufSize_ST = size;
+        }
+        if let Some(size) = self.out_step_st {
+            props.outStep_ST = size;
+        }
+
+        #[cfg(not(feature = "st"))]
+        {
+            if let Some(threads) = self.threads {
+                props.numThreads = threads;
+            }
+            if let Some(bytes) = self.memory_limit {
+                props.memUseMax = bytes;
+            }
+            if let Some(size) = self.in_buf_size_mt {
+                props.inBufSize_MT = size;
+            }
+        }
+
+        Ok(props)
+    }
+}
+
+/// Statistics of a successful [`XzMtDecoder::decode_stream`] call, from `CXzStatInfo`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XzMtDecodeStats {
+    /// Number of compressed bytes processed, excluding unrecognized data after the last stream.
+    pub in_size: u64,
+    /// Number of decompressed bytes written.
+    pub out_size: u64,
+    /// Number of streams, if known.
+    pub num_streams: Option<u64>,
+    /// Number of blocks across all streams, if known.
+    pub num_blocks: Option<u64>,
+    /// True if the input continues with data which is not another `.xz` stream.
+    pub data_after_end: bool,
+    /// Whether the file was actually decoded on multiple threads.
+    pub multithreaded: bool,
+}
+
+/// Safe owning handle around the multithreaded `.xz` decoder (`CXzDecMtHandle`).
+///
+/// With the `st` feature, or a single thread, this behaves like a streaming [`XzDecoder`].
+pub struct XzMtDecoder {
+    handle: CXzDecMtHandle,
+    props: CXzDecMtProps,
+    // Never read, but boxed and kept alive since the decoder keeps a pointer to it.
+    _alloc: Box<Allocator>,
+    progress: Option<Box<ProgressFn>>,
+}
+
+// The handle is exclusively owned and only ever accessed through `&mut self`.
+unsafe impl Send for XzMtDecoder {}
+
+impl XzMtDecoder {
+    /// Creates a new decoder with default options (a single thread).
+    ///
+    /// # Returns
+    /// * `Result<Self, LzmaError>` - The decoder, or [`LzmaError::Mem`] if allocation failed.
+    pub fn new() -> Result<Self, LzmaError> {
+        Self::with_options(&XzMtDecoderOptions::default())
+    }
+
+    /// Creates a new decoder from validated [`XzMtDecoderOptions`].
+    pub fn with_options(options: &XzMtDecoderOptions) -> Result<Self, LzmaError> {
+        init_tables();
+
+        let props = options.build()?;
+        let alloc = Box::new(Allocator::default());
+        let handle = unsafe { XzDecMt_Create(alloc.as_ref().as_ref(), alloc.as_ref().as_ref()) };
+        if handle.is_null() {
+            return Err(LzmaError::Mem);
+        }
+
+        Ok(Self {
+            handle,
+            props,
+            _alloc: alloc,
+            progress: None,
+        })
+    }
+
+    /// Sets a callback receiving `(in_size, out_size)` as decompression progresses.
+    /// Returning [`ControlFlow::Break`] cancels the operation with [`LzmaError::Cancelled`].
+    pub fn set_progress<F: FnMut(u64, u64) -> ControlFlow<()> + Send + 'static>(&mut self, progress: F) {
+        self.progress = Some(Box::new(progress));
+    }
+
+    /// Decompresses all `.xz` streams from `input` into `output`.
+    ///
+    /// The streams may be accessed from worker threads, hence the `Send` bounds.
+    ///
+    /// # Arguments
+    /// * `input` - Reader positioned at the start of the `.xz` file.
+    /// * `output` - Writer receiving the decompressed data.
+    /// * `unpacked_size` - Decompressed size if known; decoding stops once it is reached.
+    pub fn decode_stream<R: Read + Send, W: Write + Send>(
+        &mut self,
+        input: &mut R,
+        output: &mut W,
+        unpacked_size: Option<u64>,
+    ) -> io::Result<XzMtDecodeStats> {
+        let mut in_stream = SeqInStream::new(input);
+        let mut out_stream = SeqOutStream::new(output);
+        let mut progress = self.progress.as_deref_mut().map(ProgressBridge::new);
+        let progress_ptr = progress.as_mut().map_or(ptr::null(), |p| p.as_ptr());
+
+        let mut stat = CXzStatInfo::default();
+        let mut is_mt = 0;
+        let res = unsafe {
+            XzDecMt_Decode(
+                self.handle,
+                &self.props,
+                unpacked_size.as_ref().map_or(ptr::null(), |size| size),
+                // Require the streams to be complete, rather than allowing partial output.
+                1,
+                out_stream.as_ptr(),
+                in_stream.as_ptr(),
+                &mut stat,
+                &mut is_mt,
+                progress_ptr,
+            )
+        };
+
+        if let Some(e) = in_stream.take_error().or(out_stream.take_error()) {
+            return Err(e);
+        }
+        check_progress(progress.as_mut(), res)?;
+        check(stat.CombinedRes)?;
+        Ok(XzMtDecodeStats {
+            in_size: stat.InSize,
+            out_size: stat.OutSize,
+            num_streams: (stat.NumStreams_Defined != 0).then_some(stat.NumStreams),
+            num_blocks: (stat.NumBlocks_Defined != 0).then_some(stat.NumBlocks),
+            data_after_end: stat.DataAfterEnd != 0,
+            multithreaded: is_mt != 0,
+        })
+    }
+
+    /// Returns the raw decoder handle, for use with APIs not covered by this wrapper.
+    pub fn as_ptr(&self) -> CXzDecMtHandle {
+        self.handle
+    }
+}
+
+impl Drop for XzMtDecoder {
+    fn drop(&mut self) {
+        unsafe { XzDecMt_Destroy(self.handle) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_xz_mt_decoder_round_trip() {
+        let input: Vec<u8> = (0..4_000_000u32).map(|x| (x % 251) as u8 ^ (x >> 14) as u8).collect();
+        let options = XzEncoderOptions::preset(1).block_size(1 << 18).block_threads(4);
+        let compressed = XzEncoder::with_options(&options).unwrap().encode_to_vec(&input).unwrap();
+
+        for threads in [1, 4] {
+            let options = XzMtDecoderOptions::default()
+                .threads(threads)
+                .memory_limit(64 << 20);
+            let mut decoder = XzMtDecoder::with_options(&options).unwrap();
+
+            let mut output = Vec::new();
+            let stats = decoder
+                .decode_stream(&mut Cursor::new(&compressed), &mut output, None)
+                .unwrap();
+            assert_eq!(output, input);
+            assert_eq!(stats.out_size, input.len() as u64);
+            assert!(!stats.data_after_end);
+        }
+    }
+
+    #[test]
+    fn test_xz_mt_decoder_single_block_fallback() {
+        // Single-threaded encoders don't store block sizes, so these streams can't be split across threads.
+        let mut file = include_bytes!("../tests/fixtures/hello_crc32.xz").to_vec();
+        file.extend_from_slice(&[0; 4]);
+        file.extend_from_slice(include_bytes!("../tests/fixtures/hello_sha256.xz"));
+        let mut decoder = XzMtDecoder::with_options(&XzMtDecoderOptions::default().threads(4)).unwrap();
+
+        let mut output = Vec::new();
+        let stats = decoder
+            .decode_stream(&mut Cursor::new(&file), &mut output, None)
+            .unwrap();
+        assert_eq!(output, b"Hello from xz!\n".repeat(6));
+        assert_eq!(stats.num_streams, Some(2));
+        assert_eq!(stats.num_blocks, Some(2));
+        assert_eq!(stats.out_size, output.len() as u64);
+    }
+
+    #[test]
+    fn test_xz_mt_decoder_corrupt_input() {
+        let mut corrupt = include_bytes!("../tests/fixtures/hello_crc64.xz").to_vec();
+        corrupt[56] ^= 1;
+
+        let mut decoder = XzMtDecoder::new().unwrap();
+        let err = decoder
+            .decode_stream(&mut Cursor::new(corrupt), &mut Vec::new(), None)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        assert_eq!(XzMtDecoderOptions::default().memory_limit(0).build(), Err(LzmaError::Param));
+    }
+}
@@ -0,0 +1,82 @@
+use crate::buffered::{BufferedDecoder, Step, StepDecoder};
+use crate::*;
+use std::io::{self, BufRead, Read};
+
+/// Streaming `.xz` decompressor implementing [`Read`] and [`BufRead`].
+///
+/// Reads until the end of the inner reader, decoding all concatenated streams.
+pub struct XzReader<R> {
+    inner: BufferedDecoder<R, XzDecoder>,
+}
+
+impl<R: Read> XzReader<R> {
+    /// Creates a reader decoding the `.xz` file provided by `inner`.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner: BufferedDecoder::new(inner, XzDecoder::new()),
+        }
+    }
+
+    /// Returns a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        self.inner.get_ref()
+    }
+
+    /// Consumes the reader, returning the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+}
+
+impl<R: Read> BufRead for XzReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt)
+    }
+}
+
+impl<R: Read> Read for XzReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl StepDecoder for XzDecoder {
+    const FORMAT: &'static str = "xz";
+
+    fn step(&mut self, input: &[u8], output: &mut [u8], input_eof: bool) -> io::Result<Step> {
+        let progress = self.decode(input, output, input_eof)?;
+        // More streams may follow, so the file only ends with the input.
+        let finished = input_eof && progress.consumed == 0 && progress.produced == 0 && self.is_finished();
+        Ok(Step {
+            consumed: progress.consumed,
+            produced: progress.produced,
+            finished,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_xz_reader_concatenated_streams() {
+        let fixture = include_bytes!("../tests/fixtures/concatenated_padded.xz");
+        let mut output = String::new();
+        XzReader::new(Cursor::new(fixture)).read_to_string(&mut output).unwrap();
+        assert_eq!(output, "first stream\nsecond stream\n");
+    }
+
+    #[test]
+    fn test_xz_reader_truncated_stream() {
+        let fixture = include_bytes!("../tests/fixtures/hello_sha256.xz");
+        let mut reader = XzReader::new(Cursor::new(&fixture[..fixture.len() - 1]));
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
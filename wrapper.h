@@ -6,9 +6,37 @@
 #include "7z/C/LzmaEnc.h"
 #include "7z/C/Lzma2Dec.h"
 #include "7z/C/Lzma2Enc.h"
+#include "7z/C/Lzma2DecMt.h"
 #include "7z/C/LzFind.h"
 #include "7z/C/LzFindMt.h"
 #include "7z/C/LzFindOpt.c" // .c is not a typo. There is no header.
 
+// XZ Container
+#include "7z/C/Xz.h" // XzDec.c is compiled alongside, see build.rs
+#include "7z/C/XzEnc.h"
+#include "7z/C/XzCrc64.h"
+#include "7z/C/7zCrc.h"
+#include "7z/C/Sha256.h"
+#include "7z/C/Bra.h"
+#include "7z/C/Delta.h"
+#include "7z/C/CpuArch.h"
+
+// Seekable input streams
+#include "7z/C/7zTypes.h" // 7zStream.c (CLookToRead2, LookInStream_*) is compiled alongside, see build.rs
+
+// 7z Archive
+#include "7z/C/7z.h" // 7zArcIn.c and 7zDec.c are compiled alongside, see build.rs
+#include "7z/C/7zBuf.h"
+#include "7z/C/Bcj2.h"
+#include "7z/C/Ppmd7.h"
+
+// Encryption
+#include "7z/C/Aes.h" // AesOpt.c (or the precompiled AesOpt.o) provides the hardware-accelerated variants
+
+// Hashing (Sha256.h is included above)
+#include "7z/C/Sha1.h" // Sha1Opt.c/Sha256Opt.c (or the precompiled objects) provide the hardware-accelerated variants
+
 // Threading for the multithreaded logic
-#include "7z/C/Threads.h"
\ No newline at end of file
+#include "7z/C/Threads.h"
+#include "7z/C/MtCoder.h" // Used by the multithreaded LZMA2 encoder
+#include "7z/C/MtDec.h" // Used by the multithreaded LZMA2 decoder
\ No newline at end of file